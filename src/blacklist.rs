@@ -0,0 +1,568 @@
+use crate::cli::{Matcher, ParseArgsError, Pattern, Target};
+use crate::leet::LeetTable;
+use crate::util::{fold_case, normalize, word_boundaries};
+use aho_corasick::{AhoCorasick, BuildError};
+use regex::RegexSet;
+use std::borrow::Cow;
+use std::ops::Range;
+
+pub(crate) struct Blacklist {
+    patterns: Vec<Pattern>,
+    indexes: Vec<(Target, Index)>,
+}
+
+struct Index {
+    literals: Vec<Automaton>,
+    leet: Option<LeetAutomaton>,
+    words: Option<WordAutomaton>,
+    regexes: RegexSet,
+    regex_patterns: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Hit {
+    pub(crate) span: Option<Range<usize>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Transform {
+    fold: bool,
+    normalize: bool,
+}
+
+impl Transform {
+    const ALL: [Transform; 4] = [
+        Transform {
+            fold: false,
+            normalize: false,
+        },
+        Transform {
+            fold: true,
+            normalize: false,
+        },
+        Transform {
+            fold: false,
+            normalize: true,
+        },
+        Transform {
+            fold: true,
+            normalize: true,
+        },
+    ];
+
+    fn of(pattern: &Pattern) -> Option<(Self, &str)> {
+        let (fold, text) = match &pattern.matcher {
+            Matcher::Literal(text) => (false, text),
+            Matcher::FoldedLiteral(text) => (true, text),
+            Matcher::Leet(_) | Matcher::Word(_) | Matcher::RegExp(_) => return None,
+        };
+
+        Some((
+            Self {
+                fold,
+                normalize: pattern.modifiers.normalize,
+            },
+            text.as_str(),
+        ))
+    }
+
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match (self.fold, self.normalize) {
+            (false, false) => Cow::Borrowed(text),
+            (true, false) => Cow::Owned(fold_case(text)),
+            (false, true) => Cow::Owned(normalize(text)),
+            // Fold only after NFKC (e.g. 𝐏 -> P), then take the skeleton of the folded text again.
+            (true, true) => Cow::Owned(normalize(&fold_case(&normalize(text)))),
+        }
+    }
+}
+
+struct Automaton {
+    transform: Transform,
+    searcher: AhoCorasick,
+    matchers: Vec<usize>,
+}
+
+impl Automaton {
+    fn new(
+        transform: Transform,
+        patterns: impl Iterator<Item = (usize, String)>,
+    ) -> Result<Self, BuildError> {
+        let (matchers, texts): (Vec<_>, Vec<_>) = patterns.unzip();
+
+        Ok(Self {
+            transform,
+            searcher: AhoCorasick::new(texts)?,
+            matchers,
+        })
+    }
+
+    fn mark_hits(&self, content: &str, hits: &mut [Option<Hit>]) {
+        for found in self
+            .searcher
+            .find_overlapping_iter(self.transform.apply(content).as_ref())
+        {
+            hits[self.matchers[found.pattern().as_usize()]].get_or_insert(Hit { span: None });
+        }
+    }
+}
+
+struct LeetAutomaton {
+    table: LeetTable,
+    searcher: AhoCorasick,
+    matchers: Vec<usize>,
+}
+
+impl LeetAutomaton {
+    fn mark_hits(&self, content: &str, hits: &mut [Option<Hit>]) {
+        let (text, origins) = self.table.transform(content);
+
+        for found in self.searcher.find_overlapping_iter(text.as_str()) {
+            hits[self.matchers[found.pattern().as_usize()]].get_or_insert(Hit {
+                span: Some(origins[found.start()].start..origins[found.end() - 1].end),
+            });
+        }
+    }
+}
+
+struct WordAutomaton {
+    searcher: AhoCorasick,
+    matchers: Vec<usize>,
+}
+
+impl WordAutomaton {
+    fn mark_hits(&self, content: &str, hits: &mut [Option<Hit>]) {
+        let boundaries = word_boundaries(content);
+
+        for found in self.searcher.find_overlapping_iter(content) {
+            if boundaries[found.start()] && boundaries[found.end()] {
+                hits[self.matchers[found.pattern().as_usize()]].get_or_insert(Hit { span: None });
+            }
+        }
+    }
+}
+
+impl Blacklist {
+    pub(crate) fn new(
+        patterns: Vec<Pattern>,
+        leet_table: &LeetTable,
+    ) -> Result<Self, ParseArgsError> {
+        let mut indexes = Vec::<(Target, Index)>::new();
+
+        for pattern in &patterns {
+            let target = &pattern.modifiers.target;
+
+            if !indexes.iter().any(|(t, _)| t == target) {
+                indexes.push((target.clone(), Index::new(&patterns, target, leet_table)?));
+            }
+        }
+
+        Ok(Self { patterns, indexes })
+    }
+
+    pub(crate) fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    pub(crate) fn targets(&self) -> impl Iterator<Item = &Target> {
+        self.indexes.iter().map(|(target, _)| target)
+    }
+
+    pub(crate) fn find(&self, target: &Target, content: &str) -> Vec<Option<Hit>> {
+        let mut hits = Vec::new();
+        hits.resize_with(self.patterns.len(), || None);
+
+        for (_, index) in self.indexes.iter().filter(|(t, _)| t == target) {
+            index.mark_hits(content, &mut hits);
+        }
+
+        hits
+    }
+}
+
+impl Index {
+    fn new(
+        patterns: &[Pattern],
+        target: &Target,
+        leet_table: &LeetTable,
+    ) -> Result<Self, ParseArgsError> {
+        let targeted = || {
+            patterns
+                .iter()
+                .enumerate()
+                .filter(move |(_, p)| &p.modifiers.target == target)
+        };
+
+        let mut literals = Vec::new();
+
+        for transform in Transform::ALL {
+            let automaton = Automaton::new(
+                transform,
+                targeted().filter_map(|(i, p)| match Transform::of(p) {
+                    Some((t, text)) if t == transform => {
+                        Some((i, transform.apply(text).into_owned()))
+                    }
+                    _ => None,
+                }),
+            )
+            .map_err(ParseArgsError::BadAutomaton)?;
+
+            if !automaton.matchers.is_empty() {
+                literals.push(automaton);
+            }
+        }
+
+        let mut leet_patterns = Vec::new();
+        let mut leet_texts = Vec::new();
+
+        for (i, pattern) in targeted() {
+            if let Matcher::Leet(text) = &pattern.matcher {
+                let (text, _) = leet_table.transform(text);
+                if text.is_empty() {
+                    return Err(ParseArgsError::EmptyLeetPattern(pattern.origin.clone()));
+                }
+
+                leet_patterns.push(i);
+                leet_texts.push(text);
+            }
+        }
+
+        let leet = match leet_patterns.is_empty() {
+            true => None,
+            false => Some(LeetAutomaton {
+                table: leet_table.clone(),
+                searcher: AhoCorasick::new(leet_texts).map_err(ParseArgsError::BadAutomaton)?,
+                matchers: leet_patterns,
+            }),
+        };
+
+        let (word_patterns, word_texts): (Vec<_>, Vec<_>) = targeted()
+            .filter_map(|(i, p)| match &p.matcher {
+                Matcher::Word(text) => Some((i, text.as_str())),
+                _ => None,
+            })
+            .unzip();
+
+        let words = match word_patterns.is_empty() {
+            true => None,
+            false => Some(WordAutomaton {
+                searcher: AhoCorasick::new(word_texts).map_err(ParseArgsError::BadAutomaton)?,
+                matchers: word_patterns,
+            }),
+        };
+
+        let (regex_patterns, regexes): (Vec<_>, Vec<_>) = targeted()
+            .filter_map(|(i, p)| match &p.matcher {
+                Matcher::RegExp(rgx) => Some((i, rgx.as_str())),
+                _ => None,
+            })
+            .unzip();
+
+        Ok(Self {
+            regexes: RegexSet::new(regexes).map_err(ParseArgsError::BadRegexSet)?,
+            regex_patterns,
+            literals,
+            leet,
+            words,
+        })
+    }
+
+    fn mark_hits(&self, content: &str, hits: &mut [Option<Hit>]) {
+        for automaton in &self.literals {
+            automaton.mark_hits(content, hits);
+        }
+
+        if let Some(leet) = &self.leet {
+            leet.mark_hits(content, hits);
+        }
+
+        if let Some(words) = &self.words {
+            words.mark_hits(content, hits);
+        }
+
+        if !self.regex_patterns.is_empty() {
+            for i in self.regexes.matches(content).iter() {
+                hits[self.regex_patterns[i]] = Some(Hit { span: None });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Metadata, Modifiers, Origin};
+    use regex::Regex;
+    use std::cell::Cell;
+    use std::path::Path;
+    use std::rc::Rc;
+    use std::time::Instant;
+    use std::time::SystemTime;
+
+    impl Blacklist {
+        pub(crate) fn from_matchers(matchers: Vec<Matcher>) -> Self {
+            Self::with_modifiers(matchers, Modifiers::default())
+        }
+
+        pub(crate) fn with_modifiers(matchers: Vec<Matcher>, modifiers: Modifiers) -> Self {
+            let file: Rc<Path> = Path::new("test.txt").into();
+            let modifiers = Rc::new(modifiers);
+
+            Self::new(
+                matchers
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, matcher)| Pattern {
+                        matcher,
+                        origin: Origin {
+                            file: file.clone(),
+                            line: i + 1,
+                        },
+                        modifiers: modifiers.clone(),
+                        weight: 1,
+                        metadata: Metadata::default(),
+                        expiry: None,
+                        expiry_warned: Cell::new(false),
+                    })
+                    .collect(),
+                &LeetTable::default(),
+            )
+            .ok()
+            .expect("test patterns must compile")
+        }
+
+        pub(crate) fn set_expiry(&mut self, index: usize, expiry: Option<SystemTime>) {
+            self.patterns[index].expiry = expiry;
+        }
+
+        pub(crate) fn set_weights(&mut self, weights: &[i64]) {
+            for (pattern, &weight) in self.patterns.iter_mut().zip(weights) {
+                pattern.weight = weight;
+            }
+        }
+    }
+
+    fn found(hits: &[Option<Hit>]) -> Vec<bool> {
+        hits.iter().map(|hit| hit.is_some()).collect()
+    }
+
+    fn literals(v: &[&str]) -> Blacklist {
+        Blacklist::from_matchers(v.iter().map(|s| Matcher::Literal(s.to_string())).collect())
+    }
+
+    #[test]
+    fn find_reports_every_literal_hit() {
+        let blacklist = literals(&["spam", "pam", "ham", "eggs"]);
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "cheap spam and eggs")),
+            vec![true, true, false, true]
+        );
+    }
+
+    #[test]
+    fn find_maps_regex_set_hits_to_patterns() {
+        let blacklist = Blacklist::from_matchers(vec![
+            Matcher::RegExp(Regex::new("sp[a@]m").unwrap()),
+            Matcher::Literal("spam".to_string()),
+            Matcher::RegExp(Regex::new("^Re:").unwrap()),
+            Matcher::RegExp(Regex::new("eg+s").unwrap()),
+        ]);
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Re: sp@m and eggs")),
+            vec![true, false, true, true]
+        );
+        assert_eq!(blacklist.patterns()[3].origin.line, 4);
+    }
+
+    #[test]
+    fn find_folds_case_for_folded_literals() {
+        let blacklist = Blacklist::from_matchers(vec![
+            Matcher::Literal("SPAM".to_string()),
+            Matcher::FoldedLiteral("SPAM".to_string()),
+        ]);
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Spam")),
+            vec![false, true]
+        );
+    }
+
+    #[test]
+    fn find_normalizes_confusables() {
+        let blacklist = Blacklist::with_modifiers(
+            vec![
+                Matcher::Literal("paypal".to_string()),
+                Matcher::Literal("viagra".to_string()),
+            ],
+            Modifiers {
+                normalize: true,
+                ..Modifiers::default()
+            },
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your раураl account")),
+            vec![true, false]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your ｐａｙｐａｌ account")),
+            vec![true, false]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Cheap 𝐯𝐢𝐚𝐠𝐫𝐚")),
+            vec![false, true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Cheap vіаgrа")),
+            vec![false, true]
+        );
+    }
+
+    #[test]
+    fn find_combines_normalization_and_case_folding() {
+        let blacklist = Blacklist::with_modifiers(
+            vec![Matcher::FoldedLiteral("PayPal".to_string())],
+            Modifiers {
+                normalize: true,
+                ..Modifiers::default()
+            },
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your РАУРАL account")),
+            vec![true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your ＰＡＹＰＡＬ account")),
+            vec![true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your 𝐏𝐀𝐘𝐏𝐀𝐋 account")),
+            vec![true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your ℙ𝔸𝕐ℙ𝔸𝕃 account")),
+            vec![true]
+        );
+
+        let blacklist = Blacklist::with_modifiers(
+            vec![Matcher::FoldedLiteral("MONEY".to_string())],
+            Modifiers {
+                normalize: true,
+                ..Modifiers::default()
+            },
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Easy money")),
+            vec![true]
+        );
+    }
+
+    #[test]
+    fn find_only_reports_patterns_bound_to_the_target() {
+        let blacklist = Blacklist::with_modifiers(
+            vec![Matcher::Literal("BulkMailer".to_string())],
+            Modifiers {
+                target: Target::Header("x-mailer".to_string()),
+                ..Modifiers::default()
+            },
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Header("x-mailer".to_string()), "BulkMailer 3.0")),
+            vec![true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "BulkMailer 3.0")),
+            vec![false]
+        );
+        assert_eq!(
+            blacklist.targets().collect::<Vec<_>>(),
+            vec![&Target::Header("x-mailer".to_string())]
+        );
+    }
+
+    #[test]
+    fn find_without_normalize_ignores_confusables() {
+        let blacklist = literals(&["paypal"]);
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "Your раураl account")),
+            vec![false]
+        );
+    }
+
+    #[test]
+    fn find_maps_leet_hits_back_to_content() {
+        let blacklist = Blacklist::from_matchers(vec![
+            Matcher::Leet("viagra".to_string()),
+            Matcher::Leet("free money".to_string()),
+            Matcher::Literal("viagra".to_string()),
+        ]);
+        let content = "Cheap V.1.@.g.r.4 and FR33-M0N3Y!";
+        let hits = blacklist.find(&Target::Subject, content);
+        assert_eq!(found(&hits), vec![true, true, false]);
+        assert_eq!(
+            &content[hits[0].as_ref().unwrap().span.clone().unwrap()],
+            "V.1.@.g.r.4"
+        );
+        assert_eq!(
+            &content[hits[1].as_ref().unwrap().span.clone().unwrap()],
+            "FR33-M0N3Y"
+        );
+    }
+
+    #[test]
+    fn find_leet_ignores_separators() {
+        let blacklist = Blacklist::from_matchers(vec![Matcher::Leet("viagra".to_string())]);
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "v i a g r a")),
+            vec![true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "v1agra")),
+            vec![true]
+        );
+        assert_eq!(
+            found(&blacklist.find(&Target::Subject, "via green")),
+            vec![false]
+        );
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored --nocapture"]
+    fn bench_automaton_vs_naive_contains() {
+        let keyphrases: Vec<String> = (0..20_000).map(|i| format!("keyphrase{:05}x", i)).collect();
+        let subjects: Vec<String> = (0..200)
+            .map(|i| {
+                format!(
+                    "Re: weekly report #{} about keyphrase{:05}x and others",
+                    i,
+                    i * 97
+                )
+            })
+            .collect();
+
+        let blacklist = literals(&keyphrases.iter().map(|p| p.as_str()).collect::<Vec<_>>());
+
+        let start = Instant::now();
+        let mut naive_hits = 0;
+        for subject in &subjects {
+            naive_hits += keyphrases
+                .iter()
+                .filter(|p| subject.contains(p.as_str()))
+                .count();
+        }
+        let naive = start.elapsed();
+
+        let start = Instant::now();
+        let mut automaton_hits = 0;
+        for subject in &subjects {
+            automaton_hits += blacklist
+                .find(&Target::Subject, subject)
+                .iter()
+                .filter(|hit| hit.is_some())
+                .count();
+        }
+        let automaton = start.elapsed();
+
+        println!("naive contains: {:?}, automaton: {:?}", naive, automaton);
+        assert_eq!(naive_hits, automaton_hits);
+        assert!(automaton < naive);
+    }
+}
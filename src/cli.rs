@@ -1,108 +1,794 @@
+use crate::blacklist::Blacklist;
 use crate::cnt_iter::CounterIterator;
+use crate::leet::LeetTable;
+use crate::util::parse_timestamp;
 use regex::Regex;
+use std::cell::Cell;
 use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
 
 pub(crate) enum Matcher {
     Literal(String),
+    FoldedLiteral(String),
+    Leet(String),
+    Word(String),
     RegExp(Regex),
 }
 
+#[derive(Default)]
+pub(crate) struct Modifiers {
+    pub(crate) allow: bool,
+    pub(crate) normalize: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) action: Action,
+    pub(crate) reply: Reply,
+    pub(crate) target: Target,
+}
+
+impl Modifiers {
+    pub(crate) fn severity(&self) -> (Action, u16) {
+        (self.action, self.reply.code)
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) enum Action {
+    Tag,
+    Junk,
+    Tempfail,
+    #[default]
+    Reject,
+    Disconnect,
+}
+
+impl Action {
+    fn reply(self) -> Reply {
+        match self {
+            Action::Tempfail => Reply {
+                code: 451,
+                text: "4.7.1 Blacklisted keyphrase found, try again later".to_owned(),
+            },
+            Action::Tag | Action::Junk | Action::Reject => Reply::default(),
+            Action::Disconnect => Reply {
+                code: 421,
+                text: "4.7.0 Blacklisted keyphrase found, closing connection".to_owned(),
+            },
+        }
+    }
+
+    fn accepts(self, code: u16) -> bool {
+        match self {
+            Action::Tag | Action::Junk => false,
+            Action::Tempfail => (400..500).contains(&code),
+            Action::Reject => (500..600).contains(&code),
+            Action::Disconnect => (400..600).contains(&code),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Reply {
+    pub(crate) code: u16,
+    pub(crate) text: String,
+}
+
+impl Default for Reply {
+    fn default() -> Self {
+        Self {
+            code: 550,
+            text: "Blacklisted keyphrase found".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub(crate) enum Target {
+    #[default]
+    Subject,
+    FromName,
+    Header(String),
+    Body,
+    Attachment,
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Subject => write!(f, "subject"),
+            Target::FromName => write!(f, "From display name"),
+            Target::Header(name) => write!(f, "header {name}"),
+            Target::Body => write!(f, "body"),
+            Target::Attachment => write!(f, "attachment"),
+        }
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.text.is_empty() {
+            true => write!(f, "{}", self.code),
+            false => write!(f, "{} {}", self.code, self.text),
+        }
+    }
+}
+
+pub(crate) struct Pattern {
+    pub(crate) matcher: Matcher,
+    pub(crate) origin: Origin,
+    pub(crate) modifiers: Rc<Modifiers>,
+    pub(crate) weight: i64,
+    pub(crate) metadata: Metadata,
+    pub(crate) expiry: Option<SystemTime>,
+    pub(crate) expiry_warned: Cell<bool>,
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub(crate) struct Metadata {
+    pub(crate) id: Option<String>,
+    pub(crate) added: Option<String>,
+    pub(crate) expires: Option<String>,
+    pub(crate) ticket: Option<String>,
+}
+
+impl Metadata {
+    fn parse(field: &str) -> Result<Option<Self>, String> {
+        let pairs = field
+            .split_whitespace()
+            .map(|token| token.split_once('='))
+            .collect::<Option<Vec<_>>>();
+
+        let pairs = match pairs {
+            Some(pairs) if pairs.iter().all(|(key, _)| is_metadata_key(key)) => pairs,
+            _ => return Ok(None),
+        };
+
+        let mut metadata = Self::default();
+
+        for (key, value) in pairs {
+            let slot = match key {
+                "id" => &mut metadata.id,
+                "added" => &mut metadata.added,
+                "expires" => &mut metadata.expires,
+                "ticket" => &mut metadata.ticket,
+                _ => return Err(key.to_owned()),
+            };
+
+            *slot = Some(value.to_owned());
+        }
+
+        match metadata == Self::default() {
+            true => Ok(None),
+            false => Ok(Some(metadata)),
+        }
+    }
+}
+
+fn is_metadata_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+impl Display for Metadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let fields = [
+            ("id", &self.id),
+            ("added", &self.added),
+            ("expires", &self.expires),
+            ("ticket", &self.ticket),
+        ];
+
+        for (key, value) in fields {
+            if let Some(value) = value {
+                write!(f, ", {}={}", key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Origin {
+    pub(crate) file: Rc<Path>,
+    pub(crate) line: usize,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, line #{}", self.file.display(), self.line)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Options {
+    pub(crate) deny_invisible: bool,
+    pub(crate) verdict_header: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) watch: bool,
+    pub(crate) threshold: i64,
+    pub(crate) leet_table: LeetTable,
+    pub(crate) tag_prefix: Option<String>,
+    pub(crate) body_limit: Option<usize>,
+}
+
+impl Options {
+    pub(crate) fn tag_prefix(&self) -> &str {
+        self.tag_prefix.as_deref().unwrap_or("[SUSPECT]")
+    }
+
+    pub(crate) fn body_limit(&self) -> usize {
+        self.body_limit.unwrap_or(1 << 20)
+    }
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct Sources {
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) dirs: Vec<PathBuf>,
+}
+
+pub(crate) struct Config {
+    pub(crate) options: Options,
+    pub(crate) blacklist: Blacklist,
+    pub(crate) sources: Sources,
+}
+
 pub(crate) enum ParseArgsError {
+    UnknownOption,
+    BadOptionValue,
     UnknownMatcher,
+    UnknownModifier(String),
+    BadModifierValue(String),
     NoFile,
     EmptyName,
     BadFile(io::Error),
     BadLine(usize, io::Error),
     BadRegex(usize, regex::Error),
+    BadLeetEntry(usize),
+    BadExpiry(usize),
+    UnknownMetadata(usize, String),
+    BadInclude(usize, io::Error),
+    IncludeCycle(usize),
+    UnknownInclude(usize),
+    NoIncludePath(usize),
+    InDirectory(Rc<Path>, Box<ParseArgsError>),
+    Included {
+        file: Rc<Path>,
+        from: Origin,
+        inner: Box<ParseArgsError>,
+    },
+    EmptyLeetPattern(Origin),
+    BadAutomaton(aho_corasick::BuildError),
+    BadRegexSet(regex::Error),
 }
 
 pub(crate) fn blame_user(err: ParseArgsError, consumed: usize) {
     match err {
+        ParseArgsError::Included { file, from, inner } => {
+            let mut chain = vec![from];
+            let (mut file, mut err) = (file, *inner);
+
+            while let ParseArgsError::Included {
+                file: f,
+                from,
+                inner,
+            } = err
+            {
+                chain.push(from);
+                file = f;
+                err = *inner;
+            }
+
+            blame(err, &file.display().to_string());
+
+            for from in chain.iter().skip(1).rev() {
+                eprintln!("  included from {}", from);
+            }
+
+            eprintln!("  included from {} (CLI argument #{})", chain[0], consumed);
+        }
+        ParseArgsError::InDirectory(file, inner) => match *inner {
+            err @ ParseArgsError::Included { .. } => blame_user(err, consumed),
+            err => blame(
+                err,
+                &format!("CLI argument #{}, {}", consumed, file.display()),
+            ),
+        },
+        err => blame(err, &format!("CLI argument #{}", consumed)),
+    }
+}
+
+fn blame(err: ParseArgsError, place: &str) {
+    match err {
+        ParseArgsError::UnknownOption => {
+            eprintln!("Unknown option ({}).", place);
+        }
+        ParseArgsError::BadOptionValue => {
+            eprintln!("Invalid option value ({}).", place);
+        }
         ParseArgsError::UnknownMatcher => {
             eprintln!(
-                "Unknown kind of pattern (CLI argument #{}), expected \"literal\"/\"regex\".",
-                consumed
+                "Unknown kind of pattern ({}), expected \"[allow-]literal[-dir]\"/\"iliteral\"/\"leet\"/\"word\"/\"regex\".",
+                place
             );
         }
+        ParseArgsError::UnknownModifier(name) => {
+            eprintln!("Unknown or inapplicable modifier ({}): {}", place, name);
+        }
+        ParseArgsError::BadModifierValue(name) => {
+            eprintln!("Invalid value of modifier ({}): {}", place, name);
+        }
         ParseArgsError::NoFile => {
             eprintln!("Unexpected end of CLI arguments, expected file.");
         }
         ParseArgsError::EmptyName => {
+            eprintln!("Illegal empty string ({}), expected file.", place);
+        }
+        ParseArgsError::BadFile(er) => {
+            eprintln!("Inaccessible file ({}), error: {}", place, er);
+        }
+        ParseArgsError::BadLine(no, er) => {
+            eprintln!("File read error ({}, line #{}): {}", place, no, er);
+        }
+        ParseArgsError::BadRegex(no, er) => {
             eprintln!(
-                "Illegal empty string (CLI argument #{}), expected file.",
-                consumed
+                "Invalid regular expression ({}, line #{}): {}",
+                place, no, er
             );
         }
-        ParseArgsError::BadFile(er) => {
+        ParseArgsError::BadLeetEntry(no) => {
             eprintln!(
-                "Inaccessible file (CLI argument #{}), error: {}",
-                consumed, er
+                "Invalid leetspeak substitution ({}, line #{}), expected symbol and letter.",
+                place, no
             );
         }
-        ParseArgsError::BadLine(no, er) => {
+        ParseArgsError::BadExpiry(no) => {
             eprintln!(
-                "File read error (CLI argument #{}, line #{}): {}",
-                consumed, no, er
+                "Invalid expiry date ({}, line #{}), expected YYYY-MM-DD or YYYY-MM-DDTHH:MM:SSZ.",
+                place, no
             );
         }
-        ParseArgsError::BadRegex(no, er) => {
+        ParseArgsError::UnknownMetadata(no, key) => {
+            eprintln!(
+                "Unknown metadata field \"{}\" ({}, line #{}), expected \"id\", \"added\", \"expires\" or \"ticket\".",
+                key, place, no
+            );
+        }
+        ParseArgsError::BadInclude(no, er) => {
+            eprintln!(
+                "Inaccessible included file ({}, line #{}), error: {}",
+                place, no, er
+            );
+        }
+        ParseArgsError::IncludeCycle(no) => {
+            eprintln!("Include cycle ({}, line #{}).", place, no);
+        }
+        ParseArgsError::UnknownInclude(no) => {
             eprintln!(
-                "Invalid regular expression (CLI argument #{}, line #{}): {}",
-                consumed, no, er
+                "Unknown kind of included patterns ({}, line #{}), expected \"#include\" or \"#include-literal\"/\"iliteral\"/\"leet\"/\"word\"/\"regex\".",
+                place, no
             );
         }
+        ParseArgsError::NoIncludePath(no) => {
+            eprintln!("Included file missing ({}, line #{}).", place, no);
+        }
+        ParseArgsError::InDirectory(..) | ParseArgsError::Included { .. } => unreachable!(),
+        ParseArgsError::EmptyLeetPattern(origin) => {
+            eprintln!("Leetspeak pattern without any letters ({}).", origin);
+        }
+        ParseArgsError::BadAutomaton(er) => {
+            eprintln!("Failed to compile literal patterns: {}", er);
+        }
+        ParseArgsError::BadRegexSet(er) => {
+            eprintln!("Failed to compile regular expressions: {}", er);
+        }
     }
 }
 
 pub(crate) fn parse_cmdline(
     mut args: impl Iterator<Item = OsString>,
-) -> (
-    Option<OsString>,
-    Result<Vec<Matcher>, ParseArgsError>,
-    usize,
-) {
+) -> (Option<OsString>, Result<Config, ParseArgsError>, usize) {
     let program = args.next();
     let mut ci = CounterIterator::new(args);
+    let mut options = Options::default();
+    let mut sources = Sources::default();
+    let config = parse_args(&mut ci, &mut options, &mut sources)
+        .and_then(|patterns| Blacklist::new(patterns, &options.leet_table))
+        .map(|blacklist| Config {
+            options,
+            blacklist,
+            sources,
+        });
 
-    (program, parse_args(&mut ci), ci.taken())
+    (program, config, ci.taken())
 }
 
-fn parse_args(args: &mut dyn Iterator<Item = OsString>) -> Result<Vec<Matcher>, ParseArgsError> {
+fn parse_args(
+    args: &mut dyn Iterator<Item = OsString>,
+    options: &mut Options,
+    sources: &mut Sources,
+) -> Result<Vec<Pattern>, ParseArgsError> {
     let mut blacklist = Vec::new();
     loop {
         match args.next() {
             None => return Ok(blacklist),
-            Some(matcher) => match matcher.to_string_lossy().as_ref() {
-                "literal" => require_lines(args.next(), |line, _| {
-                    blacklist.push(Matcher::Literal(line));
-                    Ok(())
-                })?,
-                "regex" => require_lines(args.next(), |line, no| {
-                    blacklist.push(Matcher::RegExp(
-                        Regex::new(line.as_str())
-                            .map_err(|err| ParseArgsError::BadRegex(no, err))?,
-                    ));
-                    Ok(())
-                })?,
-                _ => return Err(ParseArgsError::UnknownMatcher),
-            },
+            Some(spec) => {
+                let spec = spec.to_string_lossy();
+                if spec.starts_with("--") {
+                    parse_option(spec.as_ref(), options, sources)?;
+                    continue;
+                }
+
+                let (mut kind, modifiers) = match spec.split_once(',') {
+                    None => (spec.as_ref(), None),
+                    Some((kind, modifiers)) => (kind, Some(modifiers)),
+                };
+                let allow = match kind.strip_prefix("allow-") {
+                    None => false,
+                    Some(rest) => {
+                        kind = rest;
+                        true
+                    }
+                };
+
+                let dir = match kind.strip_suffix("-dir") {
+                    None => false,
+                    Some(rest) => {
+                        kind = rest;
+                        true
+                    }
+                };
+
+                let on_line = line_parser(kind).ok_or(ParseArgsError::UnknownMatcher)?;
+                let modifiers = Rc::new(parse_modifiers(kind, allow, modifiers)?);
+                let path = require_file(args.next())?;
+
+                match dir {
+                    false => require_lines(path, &modifiers, &mut blacklist, on_line, sources)?,
+                    true => {
+                        sources.dirs.push(path.to_path_buf());
+
+                        for file in list_dir(&path)? {
+                            require_lines(
+                                file.clone(),
+                                &modifiers,
+                                &mut blacklist,
+                                on_line,
+                                sources,
+                            )
+                            .map_err(|err| ParseArgsError::InDirectory(file, Box::new(err)))?;
+                        }
+                    }
+                }
+            }
         }
     }
 }
 
+type LineParser = fn(String, usize) -> Result<Matcher, ParseArgsError>;
+
+fn line_parser(kind: &str) -> Option<LineParser> {
+    Some(match kind {
+        "literal" => |line, _| Ok(Matcher::Literal(line)),
+        "iliteral" => |line, _| Ok(Matcher::FoldedLiteral(line)),
+        "leet" => |line, _| Ok(Matcher::Leet(line)),
+        "word" => |line, _| Ok(Matcher::Word(line)),
+        "regex" => |line, no| {
+            Ok(Matcher::RegExp(
+                Regex::new(line.as_str()).map_err(|err| ParseArgsError::BadRegex(no, err))?,
+            ))
+        },
+        _ => return None,
+    })
+}
+
+fn parse_option(
+    spec: &str,
+    options: &mut Options,
+    sources: &mut Sources,
+) -> Result<(), ParseArgsError> {
+    match spec.split_once('=') {
+        None => match spec {
+            "--deny-invisible" => options.deny_invisible = true,
+            "--verdict-header" => options.verdict_header = true,
+            "--dry-run" => options.dry_run = true,
+            #[cfg(target_os = "linux")]
+            "--watch" => options.watch = true,
+            _ => return Err(ParseArgsError::UnknownOption),
+        },
+        Some(("--threshold", value)) => {
+            options.threshold = value.parse().map_err(|_| ParseArgsError::BadOptionValue)?;
+        }
+        Some(("--body-limit", value)) => {
+            options.body_limit = Some(value.parse().map_err(|_| ParseArgsError::BadOptionValue)?);
+        }
+        Some(("--tag-prefix", prefix)) => match prefix.is_empty() {
+            true => return Err(ParseArgsError::BadOptionValue),
+            false => options.tag_prefix = Some(prefix.to_owned()),
+        },
+        Some(("--leet-table", path)) => {
+            let mut table = LeetTable::new();
+            let file = require_file(Some(path.into()))?;
+
+            sources.files.push(file.to_path_buf());
+            read_lines(&file, |line, no| match table.parse_entry(line.as_str()) {
+                true => Ok(()),
+                false => Err(ParseArgsError::BadLeetEntry(no)),
+            })?;
+
+            options.leet_table = table;
+        }
+        Some(_) => return Err(ParseArgsError::UnknownOption),
+    }
+
+    Ok(())
+}
+
+fn parse_modifiers(
+    kind: &str,
+    allow: bool,
+    mut rest: Option<&str>,
+) -> Result<Modifiers, ParseArgsError> {
+    let mut modifiers = Modifiers {
+        allow,
+        ..Modifiers::default()
+    };
+
+    while let Some(spec) = rest {
+        if let Some(reply) = spec.strip_prefix("reply=") {
+            return match parse_reply(reply) {
+                _ if allow => Err(ParseArgsError::UnknownModifier("reply".to_owned())),
+                Some(reply) if modifiers.action.accepts(reply.code) => {
+                    modifiers.reply = reply;
+                    Ok(modifiers)
+                }
+                _ => Err(ParseArgsError::BadModifierValue("reply".to_owned())),
+            };
+        }
+
+        let name = match spec.split_once(',') {
+            None => {
+                rest = None;
+                spec
+            }
+            Some((name, tail)) => {
+                rest = Some(tail);
+                name
+            }
+        };
+
+        match name {
+            "normalize" if kind == "literal" || kind == "iliteral" => modifiers.normalize = true,
+            "from-name" => modifiers.target = Target::FromName,
+            "body" => modifiers.target = Target::Body,
+            "attachment" => modifiers.target = Target::Attachment,
+            _ if name.starts_with("header=") => {
+                modifiers.target = parse_target(&name["header=".len()..])
+                    .ok_or_else(|| ParseArgsError::BadModifierValue("header".to_owned()))?
+            }
+            "dry-run" if !allow => modifiers.dry_run = true,
+            "tag" if !allow => modifiers.action = Action::Tag,
+            "junk" if !allow => modifiers.action = Action::Junk,
+            "tempfail" if !allow => {
+                modifiers.action = Action::Tempfail;
+                modifiers.reply = Action::Tempfail.reply();
+            }
+            "disconnect" if !allow => {
+                modifiers.action = Action::Disconnect;
+                modifiers.reply = Action::Disconnect.reply();
+            }
+            _ => return Err(ParseArgsError::UnknownModifier(name.to_owned())),
+        }
+    }
+
+    Ok(modifiers)
+}
+
+fn parse_target(header: &str) -> Option<Target> {
+    let valid = !header.is_empty()
+        && header
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+
+    match header.to_ascii_lowercase() {
+        _ if !valid => None,
+        name if name == "subject" => Some(Target::Subject),
+        name => Some(Target::Header(name)),
+    }
+}
+
+fn parse_reply(reply: &str) -> Option<Reply> {
+    let (code, text) = reply.split_once(' ').unwrap_or((reply, ""));
+
+    match code.len() == 3 {
+        true => Some(Reply {
+            code: code.parse().ok()?,
+            text: text.to_owned(),
+        }),
+        false => None,
+    }
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<Rc<Path>>, ParseArgsError> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir).map_err(ParseArgsError::BadFile)? {
+        let path = entry.map_err(ParseArgsError::BadFile)?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        let skip = name.starts_with('.')
+            || name.ends_with('~')
+            || name.starts_with('#') && name.ends_with('#')
+            || name.ends_with(".bak")
+            || name.ends_with(".swp");
+
+        if !skip && fs::metadata(&path).is_ok_and(|meta| meta.is_file()) {
+            files.push(path.into());
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
 fn require_lines(
-    oarg: Option<OsString>,
-    mut on_line: impl FnMut(String, usize) -> Result<(), ParseArgsError>,
+    file: Rc<Path>,
+    modifiers: &Rc<Modifiers>,
+    blacklist: &mut Vec<Pattern>,
+    on_line: LineParser,
+    sources: &mut Sources,
+) -> Result<(), ParseArgsError> {
+    let mut includes = vec![fs::canonicalize(&file).unwrap_or_else(|_| file.to_path_buf())];
+
+    load_lines(file, modifiers, blacklist, on_line, &mut includes, sources)
+}
+
+fn load_lines(
+    file: Rc<Path>,
+    modifiers: &Rc<Modifiers>,
+    blacklist: &mut Vec<Pattern>,
+    on_line: LineParser,
+    includes: &mut Vec<PathBuf>,
+    sources: &mut Sources,
+) -> Result<(), ParseArgsError> {
+    sources.files.push(file.to_path_buf());
+
+    read_lines(&file, |line, no| {
+        match include_directive(&line, no, on_line)? {
+            None if line.starts_with('#') => Ok(()),
+            None => push_pattern(&file, line, no, modifiers, blacklist, on_line),
+            Some((on_line, path)) => {
+                let included: Rc<Path> = file.parent().unwrap_or(Path::new("")).join(path).into();
+                let canonical = fs::canonicalize(&included)
+                    .map_err(|err| ParseArgsError::BadInclude(no, err))?;
+
+                if includes.contains(&canonical) {
+                    return Err(ParseArgsError::IncludeCycle(no));
+                }
+
+                includes.push(canonical);
+
+                load_lines(
+                    included.clone(),
+                    modifiers,
+                    blacklist,
+                    on_line,
+                    includes,
+                    sources,
+                )
+                .map_err(|err| ParseArgsError::Included {
+                    file: included,
+                    from: Origin {
+                        file: file.clone(),
+                        line: no,
+                    },
+                    inner: Box::new(err),
+                })?;
+
+                includes.pop();
+                Ok(())
+            }
+        }
+    })
+}
+
+fn include_directive(
+    line: &str,
+    no: usize,
+    on_line: LineParser,
+) -> Result<Option<(LineParser, &str)>, ParseArgsError> {
+    let rest = match line.strip_prefix("#include") {
+        Some(rest) if rest.is_empty() || rest.starts_with(['-', ' ', '\t']) => rest,
+        _ => return Ok(None),
+    };
+
+    let (kind, path) = rest.split_once([' ', '\t']).unwrap_or((rest, ""));
+    let path = path.trim();
+
+    let on_line = match kind.strip_prefix('-') {
+        None => on_line,
+        Some(kind) => line_parser(kind).ok_or(ParseArgsError::UnknownInclude(no))?,
+    };
+
+    match path.is_empty() {
+        true => Err(ParseArgsError::NoIncludePath(no)),
+        false => Ok(Some((on_line, path))),
+    }
+}
+
+fn push_pattern(
+    file: &Rc<Path>,
+    line: String,
+    no: usize,
+    modifiers: &Rc<Modifiers>,
+    blacklist: &mut Vec<Pattern>,
+    on_line: LineParser,
 ) -> Result<(), ParseArgsError> {
+    let (line, metadata) = match line.rsplit_once('\t') {
+        Some((rest, field)) if !rest.is_empty() => match Metadata::parse(field) {
+            Ok(Some(metadata)) => (rest, metadata),
+            Ok(None) => (line.as_str(), Metadata::default()),
+            Err(key) => return Err(ParseArgsError::UnknownMetadata(no, key)),
+        },
+        _ => (line.as_str(), Metadata::default()),
+    };
+
+    let (weight, line) = match line.split_once('\t') {
+        Some((weight, rest)) if !rest.is_empty() => match weight.parse() {
+            Ok(weight) => (weight, rest),
+            Err(_) => (1, line),
+        },
+        _ => (1, line),
+    };
+
+    let line = match line.strip_prefix('\\') {
+        Some(rest) if rest.starts_with('#') => rest,
+        _ => line,
+    };
+
+    let expiry = match &metadata.expires {
+        None => None,
+        Some(expires) => Some(parse_timestamp(expires).ok_or(ParseArgsError::BadExpiry(no))?),
+    };
+
+    blacklist.push(Pattern {
+        matcher: on_line(line.to_owned(), no)?,
+        origin: Origin {
+            file: file.clone(),
+            line: no,
+        },
+        modifiers: modifiers.clone(),
+        weight,
+        metadata,
+        expiry,
+        expiry_warned: Cell::new(false),
+    });
+    Ok(())
+}
+
+fn require_file(oarg: Option<OsString>) -> Result<Rc<Path>, ParseArgsError> {
     let name = oarg.ok_or(ParseArgsError::NoFile)?;
     if name.is_empty() {
         return Err(ParseArgsError::EmptyName);
     }
 
+    Ok(Path::new(&name).into())
+}
+
+fn read_lines(
+    file: &Path,
+    mut on_line: impl FnMut(String, usize) -> Result<(), ParseArgsError>,
+) -> Result<(), ParseArgsError> {
     let mut ci = CounterIterator::new(
-        BufReader::new(File::open(name).map_err(|err| ParseArgsError::BadFile(err))?).lines(),
+        BufReader::new(File::open(file).map_err(ParseArgsError::BadFile)?).lines(),
     );
     loop {
         match ci.next() {
@@ -123,20 +809,101 @@ mod tests {
     use std::fs;
 
     fn args(v: &[&str]) -> impl Iterator<Item = OsString> {
-        v.iter()
-            .map(|s| OsString::from(s))
-            .collect::<Vec<_>>()
-            .into_iter()
+        v.iter().map(OsString::from).collect::<Vec<_>>().into_iter()
     }
 
     #[test]
     fn no_args_yields_empty_blacklist() {
         let (_, result, consumed) = parse_cmdline(args(&["prog"]));
         assert_eq!(consumed, 0);
-        let matchers = result.ok().expect("expected Ok result");
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
         assert_eq!(matchers.len(), 0);
     }
 
+    #[test]
+    fn deny_invisible_option_is_parsed() {
+        let (_, result, consumed) = parse_cmdline(args(&["prog", "--deny-invisible"]));
+        assert_eq!(consumed, 1);
+        let config = result.ok().expect("expected Ok result");
+        assert!(config.options.deny_invisible);
+    }
+
+    #[test]
+    fn verdict_header_option_is_parsed() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "--verdict-header"]));
+        let config = result.ok().expect("expected Ok result");
+        assert!(config.options.verdict_header);
+    }
+
+    #[test]
+    fn dry_run_option_and_modifier_are_parsed() {
+        let path = std::env::temp_dir().join("filter_dry_run_matchers.txt");
+        fs::write(&path, "lottery\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "--dry-run",
+            "literal,dry-run,tempfail",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        assert!(config.options.dry_run);
+        let modifiers = &config.blacklist.patterns()[0].modifiers;
+        assert!(modifiers.dry_run);
+        assert_eq!(modifiers.action, Action::Tempfail);
+
+        for spec in [
+            "allow-literal,dry-run",
+            "allow-literal,tag",
+            "allow-literal,reply=550 x",
+        ] {
+            let (_, result, _) = parse_cmdline(args(&["prog", spec, path.to_str().unwrap()]));
+            assert!(
+                matches!(result, Err(ParseArgsError::UnknownModifier(_))),
+                "{}",
+                spec
+            );
+        }
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn threshold_option_is_parsed() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "--threshold=-3"]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(config.options.threshold, -3);
+    }
+
+    #[test]
+    fn bad_threshold_returns_error() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "--threshold=high"]));
+        assert!(matches!(result, Err(ParseArgsError::BadOptionValue)));
+    }
+
+    #[test]
+    fn leading_number_and_tab_is_weight() {
+        let path = std::env::temp_dir().join("filter_weighted_matchers.txt");
+        fs::write(&path, "5\tspam\nham\n-2\tRe:\nno\tweight\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert!(matches!(&matchers[0].matcher, Matcher::Literal(s) if s == "spam"));
+        assert_eq!(matchers[0].weight, 5);
+        assert!(matches!(&matchers[1].matcher, Matcher::Literal(s) if s == "ham"));
+        assert_eq!(matchers[1].weight, 1);
+        assert!(matches!(&matchers[2].matcher, Matcher::Literal(s) if s == "Re:"));
+        assert_eq!(matchers[2].weight, -2);
+        assert!(matches!(&matchers[3].matcher, Matcher::Literal(s) if s == "no\tweight"));
+        assert_eq!(matchers[3].weight, 1);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn unknown_option_returns_error() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "--bogus"]));
+        assert!(matches!(result, Err(ParseArgsError::UnknownOption)));
+    }
+
     #[test]
     fn unknown_matcher_returns_error() {
         let (_, result, _) = parse_cmdline(args(&["prog", "unknown"]));
@@ -173,10 +940,594 @@ mod tests {
         let path = std::env::temp_dir().join("filter_literal_matchers.txt");
         fs::write(&path, "spam\nphishing\n").unwrap();
         let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
-        let matchers = result.ok().expect("expected Ok result");
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
         assert_eq!(matchers.len(), 2);
-        assert!(matches!(&matchers[0], Matcher::Literal(s) if s == "spam"));
-        assert!(matches!(&matchers[1], Matcher::Literal(s) if s == "phishing"));
+        assert!(matches!(&matchers[0].matcher, Matcher::Literal(s) if s == "spam"));
+        assert!(matches!(&matchers[1].matcher, Matcher::Literal(s) if s == "phishing"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn iliteral_file_loads_folded_matchers() {
+        let path = std::env::temp_dir().join("filter_iliteral_matchers.txt");
+        fs::write(&path, "SPAM\nStraße\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "iliteral", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers.len(), 2);
+        assert!(matches!(&matchers[0].matcher, Matcher::FoldedLiteral(s) if s == "SPAM"));
+        assert!(matches!(&matchers[1].matcher, Matcher::FoldedLiteral(s) if s == "Straße"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn normalize_modifier_applies_to_list() {
+        let path = std::env::temp_dir().join("filter_normalize_matchers.txt");
+        fs::write(&path, "paypal\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "literal,normalize",
+            path.to_str().unwrap(),
+            "literal",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert!(matchers[0].modifiers.normalize);
+        assert!(!matchers[1].modifiers.normalize);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn allow_prefix_marks_allow_list() {
+        let path = std::env::temp_dir().join("filter_allow_matchers.txt");
+        fs::write(&path, "ACME Corp Invoice #\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "allow-literal",
+            path.to_str().unwrap(),
+            "allow-regex",
+            path.to_str().unwrap(),
+            "literal",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert!(matches!(&matchers[0].matcher, Matcher::Literal(_)));
+        assert!(matchers[0].modifiers.allow);
+        assert!(matches!(&matchers[1].matcher, Matcher::RegExp(_)));
+        assert!(matchers[1].modifiers.allow);
+        assert!(!matchers[2].modifiers.allow);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn unknown_allow_kind_returns_error() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "allow-bogus", "x"]));
+        assert!(matches!(result, Err(ParseArgsError::UnknownMatcher)));
+    }
+
+    #[test]
+    fn reply_modifier_takes_rest_of_argument() {
+        let path = std::env::temp_dir().join("filter_reply_matchers.txt");
+        fs::write(&path, "paypal\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "literal,normalize,reply=550 5.7.1 Message refused: phishing, obviously",
+            path.to_str().unwrap(),
+            "literal",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert!(matchers[0].modifiers.normalize);
+        assert_eq!(matchers[0].modifiers.reply.code, 550);
+        assert_eq!(
+            matchers[0].modifiers.reply.to_string(),
+            "550 5.7.1 Message refused: phishing, obviously"
+        );
+        assert_eq!(
+            matchers[1].modifiers.reply.to_string(),
+            "550 Blacklisted keyphrase found"
+        );
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn bad_reply_modifier_returns_error() {
+        for spec in [
+            "literal,reply=",
+            "literal,reply=250 OK",
+            "literal,reply=5501 x",
+        ] {
+            let (_, result, _) = parse_cmdline(args(&["prog", spec, "x"]));
+            assert!(
+                matches!(result, Err(ParseArgsError::BadModifierValue(ref m)) if m == "reply"),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn tempfail_modifier_sets_action_and_reply() {
+        let path = std::env::temp_dir().join("filter_tempfail_matchers.txt");
+        fs::write(&path, "lottery\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "literal,tempfail",
+            path.to_str().unwrap(),
+            "literal,tempfail,reply=450 4.7.1 Greylisted",
+            path.to_str().unwrap(),
+            "literal",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers[0].modifiers.action, Action::Tempfail);
+        assert_eq!(
+            matchers[0].modifiers.reply.to_string(),
+            "451 4.7.1 Blacklisted keyphrase found, try again later"
+        );
+        assert_eq!(matchers[1].modifiers.action, Action::Tempfail);
+        assert_eq!(
+            matchers[1].modifiers.reply.to_string(),
+            "450 4.7.1 Greylisted"
+        );
+        assert_eq!(matchers[2].modifiers.action, Action::Reject);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn tag_and_junk_modifiers_and_prefix_option() {
+        let path = std::env::temp_dir().join("filter_tag_matchers.txt");
+        fs::write(&path, "lottery\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal,tag", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(config.blacklist.patterns()[0].modifiers.action, Action::Tag);
+        assert_eq!(config.options.tag_prefix(), "[SUSPECT]");
+
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal,junk", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(
+            config.blacklist.patterns()[0].modifiers.action,
+            Action::Junk
+        );
+
+        let (_, result, _) = parse_cmdline(args(&["prog", "--tag-prefix=***SPAM***"]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(config.options.tag_prefix(), "***SPAM***");
+
+        let (_, result, _) = parse_cmdline(args(&["prog", "--tag-prefix="]));
+        assert!(matches!(result, Err(ParseArgsError::BadOptionValue)));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn disconnect_modifier_sets_action_and_reply() {
+        let path = std::env::temp_dir().join("filter_disconnect_matchers.txt");
+        fs::write(&path, "invoice.exe\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "literal,disconnect",
+            path.to_str().unwrap(),
+            "literal,disconnect,reply=554 5.7.1 Go away",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers[0].modifiers.action, Action::Disconnect);
+        assert_eq!(
+            matchers[0].modifiers.reply.to_string(),
+            "421 4.7.0 Blacklisted keyphrase found, closing connection"
+        );
+        assert_eq!(matchers[1].modifiers.reply.to_string(), "554 5.7.1 Go away");
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn header_and_from_name_modifiers_bind_targets() {
+        let path = std::env::temp_dir().join("filter_target_matchers.txt");
+        fs::write(&path, "BulkMailer\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "literal,header=X-Mailer",
+            path.to_str().unwrap(),
+            "allow-word,from-name",
+            path.to_str().unwrap(),
+            "literal,header=SUBJECT",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(
+            matchers[0].modifiers.target,
+            Target::Header("x-mailer".to_string())
+        );
+        assert_eq!(matchers[0].modifiers.target.to_string(), "header x-mailer");
+        assert_eq!(matchers[1].modifiers.target, Target::FromName);
+        assert_eq!(matchers[2].modifiers.target, Target::Subject);
+
+        for spec in [
+            "literal,header=",
+            "literal,header=X Mailer",
+            "literal,header=To:",
+        ] {
+            let (_, result, _) = parse_cmdline(args(&["prog", spec, path.to_str().unwrap()]));
+            assert!(
+                matches!(result, Err(ParseArgsError::BadModifierValue(m)) if m == "header"),
+                "{}",
+                spec
+            );
+        }
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn body_modifier_and_limit_option() {
+        let path = std::env::temp_dir().join("filter_body_matchers.txt");
+        fs::write(&path, "casino\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "--body-limit=4096",
+            "literal,body",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(config.options.body_limit(), 4096);
+        assert_eq!(
+            config.blacklist.patterns()[0].modifiers.target,
+            Target::Body
+        );
+        assert_eq!(Options::default().body_limit(), 1 << 20);
+
+        let (_, result, _) = parse_cmdline(args(&["prog", "--body-limit=-1"]));
+        assert!(matches!(result, Err(ParseArgsError::BadOptionValue)));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn comments_escapes_and_metadata_are_parsed() {
+        let path = std::env::temp_dir().join("filter_metadata_matchers.txt");
+        fs::write(
+            &path,
+            concat!(
+                "# Phishing campaign, see OPS-7\n",
+                "paypal\tid=PH-12 added=2026-01-05 ticket=OPS-7\n",
+                "\\#hashtag\n",
+                "3\tcasino\tid=CA-1\n",
+                "tab\tseparated\n",
+                "\\no escape\n",
+                "5\t\\#winner\n",
+            ),
+        )
+        .unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers.len(), 6);
+        assert!(matches!(&matchers[0].matcher, Matcher::Literal(s) if s == "paypal"));
+        assert_eq!(matchers[0].origin.line, 2);
+        assert_eq!(
+            matchers[0].metadata.to_string(),
+            ", id=PH-12, added=2026-01-05, ticket=OPS-7"
+        );
+        assert!(matches!(&matchers[1].matcher, Matcher::Literal(s) if s == "#hashtag"));
+        assert_eq!(matchers[1].metadata, Metadata::default());
+        assert!(matches!(&matchers[2].matcher, Matcher::Literal(s) if s == "casino"));
+        assert_eq!(matchers[2].weight, 3);
+        assert_eq!(matchers[2].metadata.id.as_deref(), Some("CA-1"));
+        assert!(matches!(&matchers[3].matcher, Matcher::Literal(s) if s == "tab\tseparated"));
+        assert!(matches!(&matchers[4].matcher, Matcher::Literal(s) if s == "\\no escape"));
+        assert!(matches!(&matchers[5].matcher, Matcher::Literal(s) if s == "#winner"));
+        assert_eq!(matchers[5].weight, 5);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn unknown_metadata_field_returns_error() {
+        let path = std::env::temp_dir().join("filter_unknown_metadata.txt");
+        fs::write(&path, "casino\tid=CA-1\npaypal\towner=bob\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
+        assert!(matches!(result, Err(ParseArgsError::UnknownMetadata(2, key)) if key == "owner"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn expiry_is_parsed() {
+        let path = std::env::temp_dir().join("filter_expiry_matchers.txt");
+        fs::write(
+            &path,
+            "a\texpires=2026-11-01\nb\texpires=2026-11-01T12:30:00Z\nc\n",
+        )
+        .unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        let since_epoch = |pattern: &Pattern| {
+            pattern.expiry.map(|time| {
+                time.duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            })
+        };
+        assert_eq!(since_epoch(&matchers[0]), Some(1793491200));
+        assert_eq!(since_epoch(&matchers[1]), Some(1793536200));
+        assert_eq!(since_epoch(&matchers[2]), None);
+
+        fs::write(&path, "a\nb\texpires=2026-02-30\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
+        assert!(matches!(result, Err(ParseArgsError::BadExpiry(2))));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn include_directives_are_resolved_relative_to_including_file() {
+        let dir = std::env::temp_dir().join("filter_include_matchers");
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        fs::write(
+            dir.join("customer.txt"),
+            "acme\n#include shared/base.txt\n#include-regex shared/base.regex\n\\#include me\n",
+        )
+        .unwrap();
+        fs::write(dir.join("shared/base.txt"), "paypal\n").unwrap();
+        fs::write(dir.join("shared/base.regex"), "pay.?pal\n").unwrap();
+
+        let customer = dir.join("customer.txt");
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", customer.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers.len(), 4);
+        assert!(matches!(&matchers[0].matcher, Matcher::Literal(s) if s == "acme"));
+        assert!(matches!(&matchers[1].matcher, Matcher::Literal(s) if s == "paypal"));
+        assert_eq!(
+            matchers[1].origin.file.as_ref(),
+            dir.join("shared/base.txt")
+        );
+        assert!(matches!(&matchers[2].matcher, Matcher::RegExp(r) if r.as_str() == "pay.?pal"));
+        assert!(matches!(&matchers[3].matcher, Matcher::Literal(s) if s == "#include me"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn include_prefixed_phrases_stay_patterns() {
+        let path = std::env::temp_dir().join("filter_include_phrases.txt");
+        fs::write(
+            &path,
+            "include free shipping\ninclude-free offer\n#including comment\n",
+        )
+        .unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers.len(), 2);
+        assert!(
+            matches!(&matchers[0].matcher, Matcher::Literal(s) if s == "include free shipping")
+        );
+        assert!(matches!(&matchers[1].matcher, Matcher::Literal(s) if s == "include-free offer"));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn include_errors_carry_the_include_chain() {
+        let dir = std::env::temp_dir().join("filter_include_errors");
+        fs::create_dir_all(&dir).unwrap();
+        let top = dir.join("top.txt");
+        fs::write(&top, "a\n#include middle.txt\n").unwrap();
+        fs::write(dir.join("middle.txt"), "#include-regex bottom.txt\n").unwrap();
+        fs::write(dir.join("bottom.txt"), "ok\n(unclosed\n").unwrap();
+
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", top.to_str().unwrap()]));
+        let mut chain = Vec::new();
+        let mut err = match result {
+            Err(err) => err,
+            Ok(_) => panic!("expected Err result"),
+        };
+        while let ParseArgsError::Included { file, from, inner } = err {
+            chain.push((file, from.line));
+            err = *inner;
+        }
+        assert!(matches!(err, ParseArgsError::BadRegex(2, _)));
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].0.as_ref(), dir.join("middle.txt"));
+        assert_eq!(chain[0].1, 2);
+        assert_eq!(chain[1].0.as_ref(), dir.join("bottom.txt"));
+        assert_eq!(chain[1].1, 1);
+
+        fs::write(
+            dir.join("bottom.txt"),
+            "#include ../filter_include_errors/top.txt\n",
+        )
+        .unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", top.to_str().unwrap()]));
+        let mut err = match result {
+            Err(err) => err,
+            Ok(_) => panic!("expected Err result"),
+        };
+        while let ParseArgsError::Included { inner, .. } = err {
+            err = *inner;
+        }
+        assert!(matches!(err, ParseArgsError::IncludeCycle(1)));
+
+        let inner_error = |middle: &str| {
+            fs::write(dir.join("middle.txt"), middle).unwrap();
+            match parse_cmdline(args(&["prog", "literal", top.to_str().unwrap()])) {
+                (_, Err(ParseArgsError::Included { inner, .. }), _) => *inner,
+                _ => panic!("expected Included error"),
+            }
+        };
+        assert!(matches!(
+            inner_error("#include-reegx bottom.txt\n"),
+            ParseArgsError::UnknownInclude(1)
+        ));
+        assert!(matches!(
+            inner_error("#include-regex\n"),
+            ParseArgsError::NoIncludePath(1)
+        ));
+        assert!(matches!(
+            inner_error("#include \n"),
+            ParseArgsError::NoIncludePath(1)
+        ));
+
+        fs::write(dir.join("middle.txt"), "#include\tbottom.txt\n").unwrap();
+        fs::write(dir.join("bottom.txt"), "ok\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", top.to_str().unwrap()]));
+        assert_eq!(
+            result
+                .ok()
+                .expect("expected Ok result")
+                .blacklist
+                .patterns()
+                .len(),
+            2
+        );
+
+        fs::write(dir.join("middle.txt"), "#include missing.txt\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal", top.to_str().unwrap()]));
+        assert!(matches!(
+            result,
+            Err(ParseArgsError::Included { inner, .. }) if matches!(*inner, ParseArgsError::BadInclude(1, _))
+        ));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn dir_kind_loads_files_in_sorted_order() {
+        let dir = std::env::temp_dir().join("filter_dir_matchers");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("20-b.txt"), "bravo\n").unwrap();
+        fs::write(dir.join("10-a.txt"), "alpha\n").unwrap();
+        fs::write(dir.join(".hidden"), "hidden\n").unwrap();
+        fs::write(dir.join("10-a.txt~"), "backup\n").unwrap();
+        fs::write(dir.join("#10-a.txt#"), "autosave\n").unwrap();
+        fs::write(dir.join("30-c.txt.bak"), "backup\n").unwrap();
+        fs::write(dir.join("sub/40-d.txt"), "nested\n").unwrap();
+
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "allow-literal-dir,normalize",
+            dir.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert_eq!(matchers.len(), 2);
+        assert!(matches!(&matchers[0].matcher, Matcher::Literal(s) if s == "alpha"));
+        assert!(matches!(&matchers[1].matcher, Matcher::Literal(s) if s == "bravo"));
+        assert!(matchers[0].modifiers.allow);
+        assert!(matchers[0].modifiers.normalize);
+        assert_eq!(matchers[1].origin.file.as_ref(), dir.join("20-b.txt"));
+
+        fs::write(dir.join("15-bad.txt"), "(unclosed\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "regex-dir", dir.to_str().unwrap()]));
+        assert!(matches!(
+            result,
+            Err(ParseArgsError::InDirectory(file, inner))
+                if file.as_ref() == dir.join("15-bad.txt")
+                    && matches!(*inner, ParseArgsError::BadRegex(1, _))
+        ));
+
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            "literal-dir",
+            dir.join("missing").to_str().unwrap(),
+        ]));
+        assert!(matches!(result, Err(ParseArgsError::BadFile(_))));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn sources_are_collected() {
+        let dir = std::env::temp_dir().join("filter_sources_matchers");
+        fs::create_dir_all(dir.join("lists")).unwrap();
+        fs::write(dir.join("main.txt"), "#include lists/b.txt\n").unwrap();
+        fs::write(dir.join("lists/b.txt"), "# nothing yet\n").unwrap();
+        fs::write(dir.join("leet.txt"), "4 a\n").unwrap();
+
+        let leet = format!("--leet-table={}", dir.join("leet.txt").display());
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            leet.as_str(),
+            "literal",
+            dir.join("main.txt").to_str().unwrap(),
+            "regex-dir",
+            dir.join("lists").to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(
+            config.sources.files,
+            vec![
+                dir.join("leet.txt"),
+                dir.join("main.txt"),
+                dir.join("lists/b.txt"),
+                dir.join("lists/b.txt"),
+            ]
+        );
+        assert_eq!(config.sources.dirs, vec![dir.join("lists")]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn unknown_modifier_returns_error() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "literal,bogus", "x"]));
+        assert!(matches!(result, Err(ParseArgsError::UnknownModifier(m)) if m == "bogus"));
+    }
+
+    #[test]
+    fn normalize_modifier_rejected_for_regex() {
+        let (_, result, _) = parse_cmdline(args(&["prog", "regex,normalize", "x"]));
+        assert!(matches!(result, Err(ParseArgsError::UnknownModifier(_))));
+    }
+
+    #[test]
+    fn leet_table_option_replaces_default_table() {
+        let table = std::env::temp_dir().join("filter_leet_table.txt");
+        let path = std::env::temp_dir().join("filter_leet_matchers.txt");
+        fs::write(&table, "2 z\n").unwrap();
+        fs::write(&path, "zebra\n").unwrap();
+        let leet_table = format!("--leet-table={}", table.to_str().unwrap());
+        let (_, result, _) = parse_cmdline(args(&[
+            "prog",
+            leet_table.as_str(),
+            "leet",
+            path.to_str().unwrap(),
+        ]));
+        let config = result.ok().expect("expected Ok result");
+        assert_eq!(config.options.leet_table.transform("2ebr4").0, "zebr4");
+        assert!(
+            matches!(&config.blacklist.patterns()[0].matcher, Matcher::Leet(s) if s == "zebra")
+        );
+        fs::remove_file(&table).ok();
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn malformed_leet_table_returns_error() {
+        let table = std::env::temp_dir().join("filter_bad_leet_table.txt");
+        fs::write(&table, "2 z\n\n22 z\n").unwrap();
+        let leet_table = format!("--leet-table={}", table.to_str().unwrap());
+        let (_, result, _) = parse_cmdline(args(&["prog", leet_table.as_str()]));
+        assert!(matches!(result, Err(ParseArgsError::BadLeetEntry(3))));
+        fs::remove_file(&table).ok();
+    }
+
+    #[test]
+    fn leet_pattern_without_letters_returns_error() {
+        let path = std::env::temp_dir().join("filter_empty_leet.txt");
+        fs::write(&path, "viagra\n...\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "leet", path.to_str().unwrap()]));
+        assert!(matches!(result, Err(ParseArgsError::EmptyLeetPattern(o)) if o.line == 2));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn word_file_loads_matchers() {
+        let path = std::env::temp_dir().join("filter_word_matchers.txt");
+        fs::write(&path, "sex\n").unwrap();
+        let (_, result, _) = parse_cmdline(args(&["prog", "word", path.to_str().unwrap()]));
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
+        assert!(matches!(&matchers[0].matcher, Matcher::Word(s) if s == "sex"));
         fs::remove_file(&path).ok();
     }
 
@@ -185,9 +1536,10 @@ mod tests {
         let path = std::env::temp_dir().join("filter_regex_matchers.txt");
         fs::write(&path, r"sp[a@]m").unwrap();
         let (_, result, _) = parse_cmdline(args(&["prog", "regex", path.to_str().unwrap()]));
-        let matchers = result.ok().expect("expected Ok result");
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
         assert_eq!(matchers.len(), 1);
-        assert!(matches!(&matchers[0], Matcher::RegExp(_)));
+        assert!(matches!(&matchers[0].matcher, Matcher::RegExp(_)));
         fs::remove_file(&path).ok();
     }
 
@@ -205,8 +1557,12 @@ mod tests {
         let path = std::env::temp_dir().join("filter_empty_lines.txt");
         fs::write(&path, "\nspam\n\nphishing\n\n").unwrap();
         let (_, result, _) = parse_cmdline(args(&["prog", "literal", path.to_str().unwrap()]));
-        let matchers = result.ok().expect("expected Ok result");
+        let config = result.ok().expect("expected Ok result");
+        let matchers = config.blacklist.patterns();
         assert_eq!(matchers.len(), 2);
+        assert_eq!(matchers[0].origin.line, 2);
+        assert_eq!(matchers[1].origin.line, 4);
+        assert_eq!(&*matchers[1].origin.file, path.as_path());
         fs::remove_file(&path).ok();
     }
 }
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+#[derive(Clone)]
+pub(crate) struct LeetTable(HashMap<char, char>);
+
+impl Default for LeetTable {
+    fn default() -> Self {
+        Self(HashMap::from([
+            ('0', 'o'),
+            ('1', 'i'),
+            ('3', 'e'),
+            ('4', 'a'),
+            ('5', 's'),
+            ('7', 't'),
+            ('8', 'b'),
+            ('9', 'g'),
+            ('@', 'a'),
+            ('$', 's'),
+            ('!', 'i'),
+            ('|', 'l'),
+            ('+', 't'),
+            ('€', 'e'),
+        ]))
+    }
+}
+
+impl LeetTable {
+    pub(crate) fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub(crate) fn parse_entry(&mut self, line: &str) -> bool {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        match fields.as_slice() {
+            [symbol, letter] => match (single_char(symbol), single_char(letter)) {
+                (Some(symbol), Some(letter)) => {
+                    self.0.insert(symbol, letter);
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    pub(crate) fn transform(&self, text: &str) -> (String, Vec<Range<usize>>) {
+        let mut out = String::with_capacity(text.len());
+        let mut origins = Vec::with_capacity(text.len());
+
+        for (start, c) in text.char_indices() {
+            let origin = start..start + c.len_utf8();
+
+            match self.0.get(&c) {
+                Some(&letter) => push_lowercase(&mut out, &mut origins, letter, origin),
+                None => {
+                    if c.is_alphanumeric() {
+                        push_lowercase(&mut out, &mut origins, c, origin);
+                    }
+                }
+            }
+        }
+
+        (out, origins)
+    }
+}
+
+fn single_char(field: &str) -> Option<char> {
+    let mut chars = field.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+fn push_lowercase(
+    out: &mut String,
+    origins: &mut Vec<Range<usize>>,
+    c: char,
+    origin: Range<usize>,
+) {
+    for lower in c.to_lowercase() {
+        out.push(lower);
+
+        for _ in 0..lower.len_utf8() {
+            origins.push(origin.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transformed(text: &str) -> String {
+        LeetTable::default().transform(text).0
+    }
+
+    #[test]
+    fn transform_substitutes_and_drops_separators() {
+        assert_eq!(transformed("v1@gr4"), "viagra");
+        assert_eq!(transformed("v.i.a.g.r.a"), "viagra");
+        assert_eq!(transformed("V i A g R a"), "viagra");
+        assert_eq!(transformed("Fr€€ M0n€y!"), "freemoneyi");
+    }
+
+    #[test]
+    fn transform_maps_offsets_back() {
+        let text = "Buy v-1-@-g-r-4 now";
+        let (out, origins) = LeetTable::default().transform(text);
+        let start = out.find("viagra").unwrap();
+        let end = start + "viagra".len();
+        assert_eq!(
+            &text[origins[start].start..origins[end - 1].end],
+            "v-1-@-g-r-4"
+        );
+    }
+
+    #[test]
+    fn transform_maps_multibyte_offsets_back() {
+        let text = "€ä";
+        let (out, origins) = LeetTable::default().transform(text);
+        assert_eq!(out, "eä");
+        assert_eq!(origins, vec![0..3, 3..5, 3..5]);
+    }
+
+    #[test]
+    fn parse_entry_accepts_symbol_and_letter() {
+        let mut table = LeetTable::new();
+        assert!(table.parse_entry("2 z"));
+        assert!(table.parse_entry("\u{00A7}\ts"));
+        assert_eq!(table.transform("2ebra \u{00A7}1").0, "zebras1");
+    }
+
+    #[test]
+    fn parse_entry_rejects_malformed_lines() {
+        let mut table = LeetTable::new();
+        assert!(!table.parse_entry("2"));
+        assert!(!table.parse_entry("22 z"));
+        assert!(!table.parse_entry("2 z x"));
+    }
+}
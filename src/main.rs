@@ -1,31 +1,56 @@
+mod blacklist;
 mod cli;
 mod cnt_iter;
+mod leet;
 mod util;
+#[cfg(target_os = "linux")]
+mod watch;
 
-use cli::{Matcher, blame_user, parse_cmdline};
-use mail_parser::MessageParser;
+use cli::{Action, Config, Reply, Target, blame_user, parse_cmdline};
+use mail_parser::{HeaderForm, MessageParser};
 use std::collections::HashMap;
 use std::env::args_os;
 use std::io::{self, BufRead, Write, stderr, stdin, stdout};
 use std::process::exit;
-use util::{join_write_bytes, scan_content};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use util::{Verdict, attachment_names, body_text, encode_header, join_write_bytes, scan_content};
+
+#[derive(Default)]
+struct Session {
+    mail: Vec<u8>,
+    verdict: Option<Verdict>,
+}
 
 fn main() -> io::Result<()> {
-    let (_, rblacklist, consumed) = parse_cmdline(args_os());
-    let blacklist = match rblacklist {
+    let args = args_os().collect::<Vec<_>>();
+    let (_, rconfig, consumed) = parse_cmdline(args.iter().cloned());
+    let mut config = match rconfig {
         Err(err) => {
             blame_user(err, consumed);
             exit(1);
         }
-        Ok(blacklist) => blacklist,
+        Ok(config) => config,
+    };
+
+    let reload = Arc::new(AtomicBool::new(false));
+
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, reload.clone())?;
+
+    #[cfg(target_os = "linux")]
+    let watcher = match config.options.watch {
+        true => Some(watch::spawn(config.sources.clone(), reload.clone())?),
+        false => None,
     };
 
     let mut std_in = stdin().lock();
     let mut std_out = stdout().lock();
-    let mut std_err = stderr().lock();
+    // Not locked for good, the watcher thread logs, too.
+    let mut std_err = stderr();
 
     let mut line = Vec::<u8>::new();
-    let mut sessions = HashMap::<Vec<u8>, Vec<u8>>::new();
+    let mut sessions = HashMap::<Vec<u8>, Session>::new();
 
     loop {
         line.clear();
@@ -35,43 +60,52 @@ fn main() -> io::Result<()> {
             return Ok(());
         }
 
-        while line
-            .pop_if(|last| match last {
-                b'\r' => true,
-                b'\n' => true,
-                _ => false,
-            })
-            .is_some()
-        {}
+        if reload.swap(false, Ordering::Relaxed) {
+            match parse_cmdline(args.iter().cloned()) {
+                (_, Ok(new_config), _) => {
+                    config = new_config;
+                    writeln!(std_err, "Pattern lists reloaded")?;
+
+                    #[cfg(target_os = "linux")]
+                    if let Some(watcher) = &watcher {
+                        watcher.send(config.sources.clone()).ok();
+                    }
+                }
+                (_, Err(err), consumed) => {
+                    blame_user(err, consumed);
+                    writeln!(std_err, "Reload failed, keeping the previous pattern lists")?;
+                }
+            }
+        }
+
+        while line.pop_if(|last| matches!(last, b'\r' | b'\n')).is_some() {}
 
         let mut fields = line.split(|&sep| sep == b'|');
         match fields.next() {
-            Some(b"config") => match fields.next() {
-                Some(b"ready") => {
+            Some(b"config") => {
+                if let Some(b"ready") = fields.next() {
                     writeln!(std_out, "register|report|smtp-in|tx-begin")?;
                     writeln!(std_out, "register|filter|smtp-in|data-line")?;
                     writeln!(std_out, "register|filter|smtp-in|commit")?;
                     writeln!(std_out, "register|report|smtp-in|link-disconnect")?;
                     writeln!(std_out, "register|ready")?;
                 }
-                _ => {}
-            },
+            }
             Some(b"report") => {
                 fields.next(); // protocol version
                 fields.next(); // timestamp
                 fields.next(); // subsystem
 
-                match (fields.next(), fields.next()) {
-                    (Some(phase), Some(session)) => match phase {
+                if let (Some(phase), Some(session)) = (fields.next(), fields.next()) {
+                    match phase {
                         b"tx-begin" => {
-                            sessions.insert(session.to_owned(), Vec::new());
+                            sessions.insert(session.to_owned(), Session::default());
                         }
                         b"link-disconnect" => {
                             sessions.remove(session);
                         }
                         _ => {}
-                    },
-                    _ => {}
+                    }
                 }
             }
             Some(b"filter") => {
@@ -79,29 +113,48 @@ fn main() -> io::Result<()> {
                 fields.next(); // timestamp
                 fields.next(); // subsystem
 
-                match (fields.next(), fields.next(), fields.next()) {
-                    (Some(phase), Some(session), Some(token)) => match phase {
+                if let (Some(phase), Some(session), Some(token)) =
+                    (fields.next(), fields.next(), fields.next())
+                {
+                    match phase {
                         b"data-line" => {
-                            std_out.write_all(b"filter-dataline|")?;
-                            std_out.write_all(session)?;
-                            std_out.write_all(b"|")?;
-                            std_out.write_all(token)?;
-                            std_out.write_all(b"|")?;
+                            let mut content = Vec::new();
+                            join_write_bytes(&mut content, b"|", fields)?;
+
+                            let end = content == b".";
+
+                            match sessions.get_mut(session) {
+                                None => write_dataline(&mut std_out, session, token, &content)?,
+                                Some(sess) => {
+                                    let held = match scans_body(&config) {
+                                        true => end,
+                                        false => end || content.is_empty(),
+                                    };
+
+                                    if sess.verdict.is_none() && held {
+                                        let verdict = judge(&sess.mail, &config, &mut std_err)?;
 
-                            join_write_bytes(&mut std_out, b"|", fields.clone())?;
-                            writeln!(std_out, "")?;
+                                        release_mail(
+                                            &mut std_out,
+                                            session,
+                                            token,
+                                            &sess.mail,
+                                            &verdict,
+                                            &config,
+                                        )?;
 
-                            let mut flds = fields.clone();
+                                        sess.verdict = Some(verdict);
+                                    }
+
+                                    if !end {
+                                        sess.mail.extend_from_slice(&content);
+                                        sess.mail.push(b'\n');
+                                    }
 
-                            match (flds.next(), flds.next()) {
-                                (Some(b"."), None) => {}
-                                _ => match sessions.get_mut(session) {
-                                    None => {}
-                                    Some(mail) => {
-                                        join_write_bytes(mail, b"|", fields)?;
-                                        writeln!(mail, "")?;
+                                    if sess.verdict.is_some() {
+                                        write_dataline(&mut std_out, session, token, &content)?;
                                     }
-                                },
+                                }
                             }
                         }
                         b"commit" => {
@@ -110,47 +163,287 @@ fn main() -> io::Result<()> {
                             std_out.write_all(b"|")?;
                             std_out.write_all(token)?;
 
-                            writeln!(
-                                std_out,
-                                "|{}",
-                                if match sessions.get(session) {
-                                    None => true,
-                                    Some(mail) => match MessageParser::new().parse_headers(mail) {
-                                        None => {
-                                            writeln!(std_err, "Malformed eMail:")?;
-                                            std_err.write_all(mail)?;
-                                            writeln!(std_err, ".")?;
-                                            true
-                                        }
-                                        Some(mail) => {
-                                            let mut allow = true;
-
-                                            scan_content(
-                                                mail.subject(),
-                                                "subject",
-                                                &blacklist,
-                                                &mut allow,
-                                                &mut std_err,
-                                            )?;
-
-                                            allow
-                                        }
-                                    },
-                                } {
+                            let outcome = match sessions.get_mut(session) {
+                                None => None,
+                                Some(sess) => {
+                                    let verdict = match sess.verdict.take() {
+                                        None => judge(&sess.mail, &config, &mut std_err)?,
+                                        Some(verdict) => verdict,
+                                    };
+
+                                    decide(&verdict, &config, &mut std_err)?
+                                }
+                            };
+
+                            match outcome {
+                                None => {
                                     writeln!(std_err, "Allowing")?;
-                                    "proceed"
-                                } else {
+                                    writeln!(std_out, "|proceed")?;
+                                }
+                                Some((Action::Tag, _)) => {
+                                    writeln!(std_err, "Tagging")?;
+                                    writeln!(std_out, "|proceed")?;
+                                }
+                                Some((Action::Junk, _)) => {
+                                    writeln!(std_err, "Junking")?;
+                                    writeln!(std_out, "|junk")?;
+                                }
+                                Some((Action::Tempfail, reply)) => {
+                                    writeln!(std_err, "Deferring")?;
+                                    writeln!(std_out, "|reject|{}", reply)?;
+                                }
+                                Some((Action::Reject, reply)) => {
                                     writeln!(std_err, "Denying")?;
-                                    "reject|550 Blacklisted keyphrase found"
+                                    writeln!(std_out, "|reject|{}", reply)?;
                                 }
-                            )?;
+                                Some((Action::Disconnect, reply)) => {
+                                    writeln!(std_err, "Disconnecting")?;
+                                    writeln!(std_out, "|disconnect|{}", reply)?;
+                                    sessions.remove(session);
+                                }
+                            }
                         }
                         _ => {}
-                    },
-                    _ => {}
+                    }
                 }
             }
             _ => {}
         }
     }
 }
+
+fn judge(mail: &[u8], config: &Config, std_err: &mut dyn Write) -> io::Result<Verdict> {
+    let mut verdict = Verdict::default();
+
+    let limit = config.options.body_limit();
+    let oversized = mail.len() > limit;
+
+    let full = config.blacklist.targets().any(|target| match target {
+        Target::Body => !oversized,
+        Target::Attachment => true,
+        _ => false,
+    });
+
+    let parser = MessageParser::new();
+    let parsed = match full {
+        true => parser.parse(mail),
+        false => parser.parse_headers(mail),
+    };
+
+    match parsed {
+        None => {
+            writeln!(std_err, "Malformed eMail:")?;
+            std_err.write_all(mail)?;
+            writeln!(std_err, ".")?;
+        }
+        Some(mail) => {
+            scan_content(
+                mail.subject(),
+                &Target::Subject,
+                config,
+                &mut verdict,
+                std_err,
+            )?;
+
+            for target in config.blacklist.targets() {
+                match target {
+                    Target::Subject => {}
+                    Target::FromName => {
+                        for addr in mail.from().iter().flat_map(|from| from.iter()) {
+                            scan_content(addr.name(), target, config, &mut verdict, std_err)?;
+                        }
+                    }
+                    Target::Header(name) => {
+                        for value in mail.header_as(name.as_str(), HeaderForm::Text) {
+                            scan_content(value.as_text(), target, config, &mut verdict, std_err)?;
+                        }
+                    }
+                    Target::Body => match oversized {
+                        true => {
+                            writeln!(std_err, "Body not scanned, eMail exceeds {} bytes", limit)?
+                        }
+                        false => {
+                            let text = body_text(&mail);
+                            scan_content(Some(&text), target, config, &mut verdict, std_err)?;
+                        }
+                    },
+                    Target::Attachment => {
+                        for name in attachment_names(&mail) {
+                            scan_content(Some(name), target, config, &mut verdict, std_err)?;
+                        }
+                    }
+                }
+            }
+
+            let threshold = config.options.threshold;
+
+            writeln!(
+                std_err,
+                "Score: {} (threshold: {})",
+                verdict.score, threshold
+            )?;
+
+            if verdict.denies(threshold) && verdict.allowed {
+                writeln!(std_err, "Denial overridden by allow-list")?;
+            }
+        }
+    }
+
+    Ok(verdict)
+}
+
+fn scans_body(config: &Config) -> bool {
+    config
+        .blacklist
+        .targets()
+        .any(|target| matches!(target, Target::Body | Target::Attachment))
+}
+
+fn decide(
+    verdict: &Verdict,
+    config: &Config,
+    std_err: &mut dyn Write,
+) -> io::Result<Option<(Action, Reply)>> {
+    let threshold = config.options.threshold;
+    let outcome = verdict.outcome(threshold);
+
+    let rehearsal = match &outcome {
+        Some(_) => config.options.dry_run,
+        None => verdict.would_deny(threshold),
+    };
+
+    if rehearsal {
+        write!(std_err, "Would deny, matching patterns:")?;
+
+        for hit in verdict.hits.iter().chain(&verdict.dry_hits) {
+            write!(std_err, " ({})", hit)?;
+        }
+
+        writeln!(std_err)?;
+    }
+
+    Ok(match config.options.dry_run {
+        true => None,
+        false => outcome,
+    })
+}
+
+fn release_mail(
+    std_out: &mut dyn Write,
+    session: &[u8],
+    token: &[u8],
+    mail: &[u8],
+    verdict: &Verdict,
+    config: &Config,
+) -> io::Result<()> {
+    let mut lines = mail.split(|&sep| sep == b'\n').collect::<Vec<_>>();
+    lines.pop(); // after the last newline
+
+    let body = lines
+        .iter()
+        .position(|line| line.is_empty())
+        .unwrap_or(lines.len());
+
+    let (headers, body) = lines.split_at(body);
+    let mut lines = Vec::with_capacity(headers.len());
+    let mut spoofed = false;
+
+    for &line in headers {
+        if !matches!(line.first(), Some(b' ' | b'\t')) {
+            spoofed = config.options.verdict_header && is_header(line, "X-Subjectstrings");
+        }
+
+        if !spoofed {
+            lines.push(line);
+        }
+    }
+
+    let subject = match verdict.outcome(config.options.threshold) {
+        Some((Action::Tag, _)) if !config.options.dry_run => {
+            let prefix = config.options.tag_prefix();
+            let subject = MessageParser::new()
+                .parse_headers(mail)
+                .and_then(|mail| mail.subject().map(str::to_owned))
+                .unwrap_or_default();
+
+            match subject.starts_with(prefix) {
+                true => None,
+                false => Some(encode_header(
+                    "Subject",
+                    format!("{} {}", prefix, subject).trim_end(),
+                )),
+            }
+        }
+        _ => None,
+    };
+
+    match subject {
+        None => {
+            for line in lines {
+                write_dataline(std_out, session, token, line)?;
+            }
+        }
+        Some(subject) => {
+            let start = lines.iter().position(|line| is_header(line, "Subject"));
+
+            let end = match start {
+                None => lines.len(),
+                Some(start) => {
+                    start
+                        + 1
+                        + lines[start + 1..]
+                            .iter()
+                            .take_while(|line| matches!(line.first(), Some(b' ' | b'\t')))
+                            .count()
+                }
+            };
+
+            for line in &lines[..start.unwrap_or(end)] {
+                write_dataline(std_out, session, token, line)?;
+            }
+
+            for line in subject {
+                write_dataline(std_out, session, token, line.as_bytes())?;
+            }
+
+            for line in &lines[end..] {
+                write_dataline(std_out, session, token, line)?;
+            }
+        }
+    }
+
+    if config.options.verdict_header {
+        let summary = verdict.summary(config.options.threshold);
+
+        for line in encode_header("X-Subjectstrings", &summary) {
+            write_dataline(std_out, session, token, line.as_bytes())?;
+        }
+    }
+
+    for line in body {
+        write_dataline(std_out, session, token, line)?;
+    }
+
+    Ok(())
+}
+
+fn is_header(line: &[u8], name: &str) -> bool {
+    line.len() > name.len()
+        && line[..name.len()].eq_ignore_ascii_case(name.as_bytes())
+        && line[name.len()] == b':'
+}
+
+fn write_dataline(
+    std_out: &mut dyn Write,
+    session: &[u8],
+    token: &[u8],
+    line: &[u8],
+) -> io::Result<()> {
+    std_out.write_all(b"filter-dataline|")?;
+    std_out.write_all(session)?;
+    std_out.write_all(b"|")?;
+    std_out.write_all(token)?;
+    std_out.write_all(b"|")?;
+    std_out.write_all(line)?;
+    writeln!(std_out)
+}
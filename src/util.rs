@@ -1,5 +1,16 @@
-use crate::Matcher;
+use crate::cli::{Action, Config, Matcher, Modifiers, Origin, Reply, Target};
+use caseless::default_case_fold_str;
+use mail_parser::decoders::html::html_to_text;
+use mail_parser::{Message, MimeHeaders, PartType};
+use std::borrow::Cow;
+use std::fmt::Write as _;
 use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_normalization::UnicodeNormalization;
+use unicode_properties::UnicodeEmoji;
+use unicode_security::confusable_detection::skeleton;
+use unicode_segmentation::UnicodeSegmentation;
 
 pub(crate) fn join_write_bytes<'a>(
     writer: &mut dyn Write,
@@ -21,30 +32,469 @@ pub(crate) fn join_write_bytes<'a>(
     Ok(())
 }
 
+pub(crate) fn fold_case(text: &str) -> String {
+    default_case_fold_str(text)
+}
+
+pub(crate) fn normalize(text: &str) -> String {
+    skeleton(text.nfkc().collect::<String>().as_str()).collect()
+}
+
+pub(crate) fn word_boundaries(text: &str) -> Vec<bool> {
+    let mut boundaries = vec![false; text.len() + 1];
+
+    for (start, _) in text.split_word_bound_indices() {
+        boundaries[start] = true;
+    }
+
+    boundaries[text.len()] = true;
+    boundaries
+}
+
+pub(crate) fn parse_timestamp(text: &str) -> Option<SystemTime> {
+    let (date, time) = text.split_once('T').unwrap_or((text, "00:00:00Z"));
+    let date = parse_fields(date, '-', &[4, 2, 2])?;
+    let time = parse_fields(time.strip_suffix('Z')?, ':', &[2, 2, 2])?;
+
+    let (year, month, day) = (date[0], date[1], date[2]);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 => 28 + leap as u64,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    if time[0] > 23 || time[1] > 59 || time[2] > 59 {
+        return None;
+    }
+
+    // Days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - (month <= 2) as u64;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year % 400 * 365 + year % 400 / 4 - year % 400 / 100 + day_of_year;
+    let days = year / 400 * 146097 + day_of_era - 719468;
+
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + time[0] * 3600 + time[1] * 60 + time[2]))
+}
+
+fn parse_fields(text: &str, sep: char, widths: &[usize]) -> Option<Vec<u64>> {
+    let fields = text.split(sep).collect::<Vec<_>>();
+
+    match fields.len() == widths.len() {
+        true => fields
+            .iter()
+            .zip(widths)
+            .map(|(field, &width)| {
+                match field.len() == width && field.bytes().all(|b| b.is_ascii_digit()) {
+                    true => field.parse().ok(),
+                    false => None,
+                }
+            })
+            .collect(),
+        false => None,
+    }
+}
+
+pub(crate) fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{00AD}'
+        | '\u{034F}'
+        | '\u{061C}'
+        | '\u{115F}'..='\u{1160}'
+        | '\u{17B4}'..='\u{17B5}'
+        | '\u{180B}'..='\u{180F}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{206F}'
+        | '\u{3164}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FEFF}'
+        | '\u{FFA0}'
+        | '\u{FFF0}'..='\u{FFF8}'
+        | '\u{1BCA0}'..='\u{1BCA3}'
+        | '\u{1D173}'..='\u{1D17A}'
+        | '\u{E0000}'..='\u{E0FFF}'
+    )
+}
+
+fn is_emoji_base(c: char) -> bool {
+    !c.is_ascii() && c.is_emoji_char()
+}
+
+fn is_tag_spec(c: &char) -> bool {
+    matches!(c, '\u{E0020}'..='\u{E007E}')
+}
+
+/// Tells whether the invisible `chars[i]` just glues an emoji sequence together.
+fn is_emoji_component(chars: &[char], i: usize) -> bool {
+    let prev = i.checked_sub(1).map(|i| chars[i]);
+    let next = chars.get(i + 1).copied();
+
+    match chars[i] {
+        '\u{200D}' => {
+            prev.is_some_and(|c| c == '\u{FE0F}' || is_emoji_base(c))
+                && next.is_some_and(is_emoji_base)
+        }
+        '\u{FE0E}' | '\u{FE0F}' => prev.is_some_and(|c| match c.is_ascii() {
+            true => matches!(c, '0'..='9' | '#' | '*') && next == Some('\u{20E3}'),
+            false => c.is_emoji_char(),
+        }),
+        '\u{E0020}'..='\u{E007F}' => {
+            let before = chars[..i].iter().rev().find(|c| !is_tag_spec(c));
+            let after = chars[i..].iter().find(|c| !is_tag_spec(c));
+
+            before == Some(&'\u{1F3F4}')
+                && after == Some(&'\u{E007F}')
+                && (is_tag_spec(&chars[i]) || prev.is_some_and(|c| is_tag_spec(&c)))
+        }
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Verdict {
+    pub(crate) denied: bool,
+    pub(crate) allowed: bool,
+    pub(crate) score: i64,
+    pub(crate) worst: Option<Rc<Modifiers>>,
+    pub(crate) hits: Vec<Origin>,
+    pub(crate) dry_score: i64,
+    pub(crate) dry_hits: Vec<Origin>,
+}
+
+impl Verdict {
+    pub(crate) fn denies(&self, threshold: i64) -> bool {
+        self.denied || self.score > threshold
+    }
+
+    pub(crate) fn allows(&self, threshold: i64) -> bool {
+        self.allowed || !self.denies(threshold)
+    }
+
+    pub(crate) fn would_deny(&self, threshold: i64) -> bool {
+        !self.allowed && (self.denied || self.score + self.dry_score > threshold)
+    }
+
+    pub(crate) fn summary(&self, threshold: i64) -> String {
+        let mut summary = String::from(match (self.hits.is_empty(), self.allowed) {
+            (true, _) => "clean",
+            (false, false) => "hit",
+            (false, true) => "allowed",
+        });
+
+        let mut lists = Vec::<(Cow<str>, Vec<usize>)>::new();
+
+        for hit in &self.hits {
+            let list = hit.file.file_stem().unwrap_or_default().to_string_lossy();
+
+            match lists.iter_mut().find(|(name, _)| *name == list) {
+                None => lists.push((list, vec![hit.line])),
+                Some((_, lines)) => {
+                    if !lines.contains(&hit.line) {
+                        lines.push(hit.line);
+                    }
+                }
+            }
+        }
+
+        for (list, lines) in lists {
+            write!(summary, "; list={}; pattern=", list).unwrap();
+
+            for (i, line) in lines.into_iter().enumerate() {
+                match i {
+                    0 => write!(summary, "line{}", line).unwrap(),
+                    _ => write!(summary, ", line{}", line).unwrap(),
+                }
+            }
+        }
+
+        write!(summary, "; score={}", self.score).unwrap();
+
+        if self.denied {
+            summary.push_str("; invisible=yes");
+        }
+
+        if !self.allows(threshold) {
+            summary.push_str("; denied=yes");
+        }
+
+        summary
+    }
+
+    pub(crate) fn outcome(&self, threshold: i64) -> Option<(Action, Reply)> {
+        match self.allows(threshold) {
+            true => None,
+            false => {
+                let rejection = (Action::default(), Reply::default());
+
+                Some(match &self.worst {
+                    None => rejection,
+                    // Invisible characters deny like a plain pattern hit, softer lists can't override that.
+                    Some(worst)
+                        if self.denied && worst.severity() < (rejection.0, rejection.1.code) =>
+                    {
+                        rejection
+                    }
+                    Some(worst) => (worst.action, worst.reply.clone()),
+                })
+            }
+        }
+    }
+}
+
+pub(crate) fn encode_header(name: &str, value: &str) -> Vec<String> {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        let mut lines = vec![format!("{}:", name)];
+
+        for word in value.split(' ') {
+            let line = lines.last_mut().unwrap();
+
+            // Fold before the space, so that unfolding restores the value.
+            if !word.is_empty() && line.len() > name.len() + 1 && line.len() + 1 + word.len() > 78 {
+                lines.push(format!(" {}", word));
+            } else {
+                line.push(' ');
+                line.push_str(word);
+            }
+        }
+
+        return lines;
+    }
+
+    let mut lines = Vec::new();
+    let mut chunk = String::new();
+
+    for c in value.chars() {
+        // 45 bytes are 60 base64 characters, that keeps every encoded-word below 75 ones.
+        if chunk.len() + c.len_utf8() > 45 {
+            lines.push(encode_word(&chunk));
+            chunk.clear();
+        }
+
+        chunk.push(c);
+    }
+
+    lines.push(encode_word(&chunk));
+    lines[0] = format!("{}: {}", name, lines[0]);
+
+    for line in &mut lines[1..] {
+        line.insert(0, ' ');
+    }
+
+    lines
+}
+
+fn encode_word(text: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut word = String::from("=?UTF-8?B?");
+
+    for chunk in text.as_bytes().chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => word.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char),
+                false => word.push('='),
+            }
+        }
+    }
+
+    word.push_str("?=");
+    word
+}
+
+pub(crate) fn body_text(mail: &Message) -> String {
+    let mut seen = Vec::new();
+    let mut text = String::new();
+
+    for &id in mail.text_body.iter().chain(&mail.html_body) {
+        if seen.contains(&id) {
+            continue;
+        }
+
+        seen.push(id);
+
+        let content = match mail.part(id).map(|part| &part.body) {
+            Some(PartType::Text(content)) => Cow::Borrowed(content.as_ref()),
+            Some(PartType::Html(content)) => Cow::Owned(html_to_text(content)),
+            _ => continue,
+        };
+
+        if !text.is_empty() {
+            text.push('\n');
+        }
+
+        text.push_str(&content);
+    }
+
+    text
+}
+
+pub(crate) fn attachment_names<'x>(mail: &'x Message) -> Vec<&'x str> {
+    let mut names = Vec::new();
+
+    for part in &mail.parts {
+        let params = [
+            (part.content_disposition(), "filename"),
+            (part.content_type(), "name"),
+        ];
+
+        for (header, attribute) in params {
+            if let Some(name) = header.and_then(|header| header.attribute(attribute)) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        if let PartType::Message(nested) = &part.body {
+            for name in attachment_names(nested) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    names
+}
+
 pub(crate) fn scan_content(
     content: Option<&str>,
-    kind: &str,
-    blacklist: &Vec<Matcher>,
-    allow: &mut bool,
+    target: &Target,
+    config: &Config,
+    verdict: &mut Verdict,
     std_err: &mut dyn Write,
 ) -> io::Result<()> {
     match content {
         None => {}
         Some(content) => {
-            for keyphrase in blacklist {
-                match keyphrase {
-                    Matcher::Literal(text) => {
-                        if content.contains(text) {
-                            writeln!(std_err, "Forbidden literal found in {}: {}", kind, text)?;
+            let mut invisible = Vec::new();
+            for c in content.chars().filter(|&c| is_invisible(c)) {
+                if !invisible.contains(&c) {
+                    invisible.push(c);
+                }
+            }
 
-                            *allow = false;
-                        }
+            let content = match invisible.is_empty() {
+                true => Cow::Borrowed(content),
+                false => {
+                    let deny = config.options.deny_invisible && *target == Target::Subject && {
+                        let chars: Vec<char> = content.chars().collect();
+
+                        chars
+                            .iter()
+                            .enumerate()
+                            .any(|(i, &c)| is_invisible(c) && !is_emoji_component(&chars, i))
+                    };
+
+                    write!(
+                        std_err,
+                        "{} {}:",
+                        match deny {
+                            true => "Forbidden invisible characters found in",
+                            false => "Invisible characters stripped from",
+                        },
+                        target
+                    )?;
+
+                    for c in invisible {
+                        write!(std_err, " U+{:04X}", c as u32)?;
+                    }
+
+                    writeln!(std_err)?;
+
+                    if deny {
+                        verdict.denied = true;
+                    }
+
+                    Cow::Owned(content.chars().filter(|&c| !is_invisible(c)).collect())
+                }
+            };
+
+            let blacklist = &config.blacklist;
+            let hits = blacklist.find(target, &content);
+            let now = SystemTime::now();
+
+            for (keyphrase, hit) in blacklist.patterns().iter().zip(hits) {
+                let hit = match hit {
+                    None => continue,
+                    Some(hit) => hit,
+                };
+
+                let (description, text) = match &keyphrase.matcher {
+                    Matcher::Literal(text) => ("literal", text.as_str()),
+                    Matcher::FoldedLiteral(text) => ("case-insensitive literal", text.as_str()),
+                    Matcher::Leet(text) => ("leetspeak literal", text.as_str()),
+                    Matcher::Word(text) => ("word", text.as_str()),
+                    Matcher::RegExp(rgx) => ("regex", rgx.as_str()),
+                };
+
+                if keyphrase.expiry.is_some_and(|expiry| expiry <= now) {
+                    if !keyphrase.expiry_warned.replace(true) {
+                        writeln!(
+                            std_err,
+                            "Expired {} would have matched in {}: {} ({}{})",
+                            description, target, text, keyphrase.origin, keyphrase.metadata
+                        )?;
+                    }
+
+                    continue;
+                }
+
+                write!(
+                    std_err,
+                    "{} {} found in {}: {} ({}{}",
+                    match keyphrase.modifiers.allow {
+                        true => "Allowed",
+                        false => "Forbidden",
+                    },
+                    description,
+                    target,
+                    text,
+                    keyphrase.origin,
+                    keyphrase.metadata
+                )?;
+
+                if keyphrase.weight != 1 {
+                    write!(std_err, ", weight {}", keyphrase.weight)?;
+                }
+
+                match keyphrase.modifiers.dry_run {
+                    true => write!(std_err, ", dry run)")?,
+                    false => write!(std_err, ")")?,
+                }
+
+                match hit.span {
+                    None => writeln!(std_err)?,
+                    Some(span) => writeln!(std_err, " as: {}", &content[span])?,
+                }
+
+                match keyphrase.modifiers.allow {
+                    true => verdict.allowed = true,
+                    false if keyphrase.modifiers.dry_run => {
+                        verdict.dry_score += keyphrase.weight;
+                        verdict.dry_hits.push(keyphrase.origin.clone());
                     }
-                    Matcher::RegExp(rgx) => {
-                        if rgx.find(content).is_some() {
-                            writeln!(std_err, "Forbidden regex found in {}: {}", kind, rgx)?;
+                    false => {
+                        verdict.score += keyphrase.weight;
+                        verdict.hits.push(keyphrase.origin.clone());
 
-                            *allow = false;
+                        let modifiers = &keyphrase.modifiers;
+                        if keyphrase.weight > 0
+                            && verdict
+                                .worst
+                                .as_ref()
+                                .is_none_or(|worst| modifiers.severity() > worst.severity())
+                        {
+                            verdict.worst = Some(modifiers.clone());
                         }
                     }
                 }
@@ -58,7 +508,17 @@ pub(crate) fn scan_content(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::cli::Matcher;
+    use crate::blacklist::Blacklist;
+    use crate::cli::{Options, Sources};
+    use mail_parser::MessageParser;
+
+    fn config(blacklist: Blacklist) -> Config {
+        Config {
+            options: Options::default(),
+            blacklist,
+            sources: Sources::default(),
+        }
+    }
 
     #[test]
     fn join_write_bytes_empty() {
@@ -83,92 +543,905 @@ mod tests {
         assert_eq!(buf, b"a|b|c");
     }
 
+    #[test]
+    fn encode_header_keeps_ascii() {
+        assert_eq!(
+            encode_header("Subject", "[SUSPECT] Hello"),
+            vec!["Subject: [SUSPECT] Hello"]
+        );
+    }
+
+    #[test]
+    fn encode_header_folds_long_ascii_values() {
+        let value = format!(
+            "[SUSPECT] {}",
+            "Limited offer, act now ".repeat(10).trim_end()
+        );
+        let lines = encode_header("Subject", &value);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= 78));
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        assert_eq!(lines.concat(), format!("Subject: {}", value));
+    }
+
+    #[test]
+    fn encode_header_uses_encoded_words() {
+        assert_eq!(
+            encode_header("Subject", "[SUSPECT] Grüße"),
+            vec!["Subject: =?UTF-8?B?W1NVU1BFQ1RdIEdyw7zDn2U=?="]
+        );
+
+        let lines = encode_header("Subject", &"ä".repeat(30));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with(" =?UTF-8?B?"));
+        assert!(lines.iter().all(|line| line.trim_start().len() <= 84));
+    }
+
+    #[test]
+    fn summary_lists_hits_and_score() {
+        assert_eq!(Verdict::default().summary(0), "clean; score=0");
+
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        let mut blacklist = Blacklist::from_matchers(vec![
+            Matcher::Literal("casino".to_string()),
+            Matcher::Literal("bonus".to_string()),
+        ]);
+        blacklist.set_weights(&[5, 2]);
+        scan_content(
+            Some("Casino bonus"),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert_eq!(
+            verdict.summary(10),
+            "hit; list=test; pattern=line2; score=2"
+        );
+        assert_eq!(
+            verdict.summary(1),
+            "hit; list=test; pattern=line2; score=2; denied=yes"
+        );
+
+        verdict.allowed = true;
+        assert_eq!(
+            verdict.summary(1),
+            "allowed; list=test; pattern=line2; score=2"
+        );
+
+        let blacklist = Blacklist::from_matchers(
+            (0..200)
+                .map(|i| Matcher::Literal(format!("w{}", i)))
+                .collect(),
+        );
+        let content = (0..200).map(|i| format!("w{} ", i)).collect::<String>();
+        let mut verdict = Verdict::default();
+        scan_content(
+            Some(&content),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        let summary = verdict.summary(0);
+        assert!(summary.starts_with("hit; list=test; pattern=line1, line2, line3, "));
+        assert_eq!(summary.matches("list=").count(), 1);
+        let lines = encode_header("X-Subjectstrings", &summary);
+        assert!(lines.iter().all(|line| line.len() <= 78));
+        assert_eq!(lines.concat(), format!("X-Subjectstrings: {}", summary));
+    }
+
+    #[test]
+    fn scan_content_dry_run_list_only_would_deny() {
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Lottery winner"),
+            &Target::Subject,
+            &config(Blacklist::with_modifiers(
+                vec![Matcher::FoldedLiteral("lottery".to_string())],
+                Modifiers {
+                    dry_run: true,
+                    ..Modifiers::default()
+                },
+            )),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(verdict.allows(0));
+        assert!(verdict.would_deny(0));
+        assert_eq!(verdict.dry_hits.len(), 1);
+        let err = String::from_utf8(err_buf).unwrap();
+        assert!(err.contains("(test.txt, line #1, dry run)\n"));
+    }
+
+    #[test]
+    fn body_text_decodes_plain_and_html_parts() {
+        let raw = concat!(
+            "Subject: Hi\n",
+            "Content-Type: multipart/alternative; boundary=b\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/plain; charset=utf-8\n",
+            "Content-Transfer-Encoding: base64\n",
+            "\n",
+            "Q2hlYXAgcGlsbHM=\n",
+            "--b\n",
+            "Content-Type: text/html\n",
+            "\n",
+            "<p>Visit our <b>casino</b></p>\n",
+            "--b--\n",
+        );
+        let mail = MessageParser::new().parse(raw.as_bytes()).unwrap();
+
+        let text = body_text(&mail);
+        assert!(text.starts_with("Cheap pills\n"));
+        assert!(text.contains("Visit our casino"));
+        assert!(!text.contains("<b>"));
+    }
+
+    #[test]
+    fn attachment_names_are_decoded() {
+        let raw = concat!(
+            "Subject: Hi\n",
+            "Content-Type: multipart/mixed; boundary=b\n",
+            "\n",
+            "--b\n",
+            "Content-Type: text/plain\n",
+            "\n",
+            "See attachments\n",
+            "--b\n",
+            "Content-Type: application/octet-stream; name=\"Invoice_2024.pdf.exe\"\n",
+            "Content-Disposition: attachment; filename=\"Invoice_2024.pdf.exe\"\n",
+            "\n",
+            "TVo=\n",
+            "--b\n",
+            "Content-Type: application/octet-stream; name=\"=?UTF-8?Q?Rechnung=5F=C3=BC.zip?=\"\n",
+            "Content-Disposition: attachment;\n",
+            " filename*0*=UTF-8''Bestellung%20;\n",
+            " filename*1*=%C3%BC.js\n",
+            "\n",
+            "TVo=\n",
+            "--b--\n",
+        );
+        let mail = MessageParser::new().parse(raw.as_bytes()).unwrap();
+
+        assert_eq!(
+            attachment_names(&mail),
+            vec!["Invoice_2024.pdf.exe", "Bestellung ü.js", "Rechnung_ü.zip"]
+        );
+    }
+
+    #[test]
+    fn outcome_defaults_to_reject() {
+        let verdict = Verdict {
+            denied: true,
+            ..Verdict::default()
+        };
+        let (action, reply) = verdict.outcome(0).unwrap();
+        assert_eq!(action, Action::Reject);
+        assert_eq!(reply.code, 550);
+        assert!(Verdict::default().outcome(0).is_none());
+    }
+
+    #[test]
+    fn parse_timestamp_accepts_dates_and_times() {
+        let secs = |text| {
+            parse_timestamp(text).map(|time| time.duration_since(UNIX_EPOCH).unwrap().as_secs())
+        };
+        assert_eq!(secs("1970-01-01"), Some(0));
+        assert_eq!(secs("2000-03-01"), Some(951868800));
+        assert_eq!(secs("2024-02-29T23:59:59Z"), Some(1709251199));
+        assert_eq!(secs("2023-02-29"), None);
+        assert_eq!(secs("1969-12-31"), None);
+        assert_eq!(secs("2024-1-01"), None);
+        assert_eq!(secs("2024-01-01T12:00:00"), None);
+        assert_eq!(secs("2024-01-01T24:00:00Z"), None);
+    }
+
+    #[test]
+    fn scan_content_skips_expired_patterns_and_warns_once() {
+        let mut blacklist = Blacklist::from_matchers(vec![
+            Matcher::Literal("lottery".to_string()),
+            Matcher::Literal("winner".to_string()),
+        ]);
+        blacklist.set_expiry(0, parse_timestamp("2020-01-01"));
+        blacklist.set_expiry(1, parse_timestamp("2999-01-01"));
+        let config = config(blacklist);
+
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("lottery"),
+            &Target::Subject,
+            &config,
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        scan_content(
+            Some("lottery winner"),
+            &Target::Subject,
+            &config,
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert_eq!(verdict.score, 1);
+
+        let err = String::from_utf8(err_buf).unwrap();
+        assert_eq!(
+            err.matches(
+                "Expired literal would have matched in subject: lottery (test.txt, line #1)\n"
+            )
+            .count(),
+            1
+        );
+        assert!(err.contains("Forbidden literal found in subject: winner"));
+    }
+
     #[test]
     fn scan_content_none_is_noop() {
-        let mut allow = true;
+        let mut verdict = Verdict::default();
         let mut err_buf = Vec::new();
-        scan_content(None, "subject", &vec![], &mut allow, &mut err_buf).unwrap();
-        assert!(allow);
+        scan_content(
+            None,
+            &Target::Subject,
+            &config(Blacklist::from_matchers(vec![])),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(verdict.allows(0));
         assert!(err_buf.is_empty());
     }
 
     #[test]
     fn scan_content_literal_match_denies() {
-        let blacklist = vec![Matcher::Literal("spam".to_string())];
-        let mut allow = true;
+        let blacklist = Blacklist::from_matchers(vec![Matcher::Literal("spam".to_string())]);
+        let mut verdict = Verdict::default();
         let mut err_buf = Vec::new();
         scan_content(
             Some("This is spam content"),
-            "body",
-            &blacklist,
-            &mut allow,
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
             &mut err_buf,
         )
         .unwrap();
-        assert!(!allow);
+        assert!(!verdict.allows(0));
     }
 
     #[test]
     fn scan_content_literal_no_match_allows() {
-        let blacklist = vec![Matcher::Literal("spam".to_string())];
-        let mut allow = true;
+        let blacklist = Blacklist::from_matchers(vec![Matcher::Literal("spam".to_string())]);
+        let mut verdict = Verdict::default();
         let mut err_buf = Vec::new();
         scan_content(
             Some("This is clean content"),
-            "body",
-            &blacklist,
-            &mut allow,
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(verdict.allows(0));
+    }
+
+    fn scan_folded(content: &str, pattern: &str) -> bool {
+        let blacklist = Blacklist::from_matchers(vec![Matcher::FoldedLiteral(pattern.to_string())]);
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some(content),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        !verdict.allows(0)
+    }
+
+    #[test]
+    fn fold_case_is_full_unicode_folding() {
+        assert_eq!(fold_case("SPAM"), "spam");
+        assert_eq!(fold_case("Straße"), "strasse");
+        assert_eq!(fold_case("İ"), "i\u{307}");
+        assert_eq!(fold_case("ΣΑΣ"), "σασ");
+    }
+
+    #[test]
+    fn scan_content_folded_literal_ignores_case() {
+        assert!(scan_folded("Cheap SPAM here", "spam"));
+        assert!(scan_folded("Cheap Spam here", "SPAM"));
+        assert!(!scan_folded("Cheap ham here", "spam"));
+    }
+
+    #[test]
+    fn scan_content_folded_literal_german_sharp_s() {
+        assert!(scan_folded("GROSSE Rabatte", "große"));
+        assert!(scan_folded("Große Rabatte", "GROSSE"));
+        assert!(scan_folded("Große Rabatte", "grosse"));
+    }
+
+    #[test]
+    fn scan_content_folded_literal_turkish_dotted_i() {
+        assert!(scan_folded("Welcome to İSTANBUL", "İstanbul"));
+        assert!(scan_folded("Welcome to i\u{307}stanbul", "İSTANBUL"));
+        assert!(!scan_folded("Welcome to ıstanbul", "istanbul"));
+    }
+
+    #[test]
+    fn scan_content_reports_every_matched_literal() {
+        let blacklist = Blacklist::from_matchers(vec![
+            Matcher::Literal("cheap".to_string()),
+            Matcher::Literal("pills".to_string()),
+            Matcher::Literal("casino".to_string()),
+        ]);
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("cheap pills"),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(!verdict.allows(0));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Forbidden literal found in subject: cheap (test.txt, line #1)\n\
+             Forbidden literal found in subject: pills (test.txt, line #2)\n"
+        );
+    }
+
+    #[test]
+    fn word_boundaries_follow_uax29() {
+        let text = "Sussex sex-shop";
+        let boundaries = word_boundaries(text);
+        let positions = (0..=text.len())
+            .filter(|&i| boundaries[i])
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![0, 6, 7, 10, 11, 15]);
+    }
+
+    #[test]
+    fn scan_content_word_requires_word_boundaries() {
+        for (subject, denied) in [
+            ("Sussex county newsletter", false),
+            ("Essex and Wessex", false),
+            ("Hot sex tonight", true),
+            ("sex", true),
+            ("Sussex sex-shop", true),
+            ("\"sex\"!", true),
+        ] {
+            let mut verdict = Verdict::default();
+            let mut err_buf = Vec::new();
+            scan_content(
+                Some(subject),
+                &Target::Subject,
+                &config(Blacklist::from_matchers(vec![Matcher::Word(
+                    "sex".to_string(),
+                )])),
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+            assert_eq!(!verdict.allows(0), denied, "{}", subject);
+        }
+    }
+
+    #[test]
+    fn scan_content_word_handles_non_ascii_scripts() {
+        for (pattern, subject, denied) in [
+            ("Kauf", "Jetzt Kauf!", true),
+            ("Kauf", "Jetzt Kaufhaus", false),
+            ("купить", "Купить купить сейчас", true),
+            ("купить", "закупить", false),
+            ("über", "Für übermorgen", false),
+            ("über", "Alles über uns", true),
+        ] {
+            let mut verdict = Verdict::default();
+            let mut err_buf = Vec::new();
+            scan_content(
+                Some(subject),
+                &Target::Subject,
+                &config(Blacklist::from_matchers(vec![Matcher::Word(
+                    pattern.to_string(),
+                )])),
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+            assert_eq!(!verdict.allows(0), denied, "{} in {}", pattern, subject);
+        }
+    }
+
+    #[test]
+    fn is_invisible_covers_common_tricks() {
+        for c in [
+            '\u{200B}', '\u{00AD}', '\u{2060}', '\u{202E}', '\u{2066}', '\u{FEFF}',
+        ] {
+            assert!(is_invisible(c));
+        }
+        for c in ['a', ' ', '\u{00A0}', 'ß'] {
+            assert!(!is_invisible(c));
+        }
+    }
+
+    #[test]
+    fn scan_content_strips_invisible_characters() {
+        let blacklist = Blacklist::from_matchers(vec![Matcher::Literal("Viagra".to_string())]);
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Cheap V\u{200B}ia\u{00AD}gr\u{202E}a"),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
             &mut err_buf,
         )
         .unwrap();
-        assert!(allow);
+        assert!(!verdict.allows(0));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Invisible characters stripped from subject: U+200B U+00AD U+202E\n\
+             Forbidden literal found in subject: Viagra (test.txt, line #1)\n"
+        );
+    }
+
+    #[test]
+    fn scan_content_invisible_characters_alone_allow_by_default() {
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Hello\u{200B}\u{200B} world"),
+            &Target::Subject,
+            &config(Blacklist::from_matchers(vec![])),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(verdict.allows(0));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Invisible characters stripped from subject: U+200B\n"
+        );
+    }
+
+    #[test]
+    fn scan_content_deny_invisible_denies() {
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Hello\u{2060} world"),
+            &Target::Subject,
+            &Config {
+                options: Options {
+                    deny_invisible: true,
+                    ..Options::default()
+                },
+                blacklist: Blacklist::from_matchers(vec![]),
+                sources: Sources::default(),
+            },
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(!verdict.allows(0));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Forbidden invisible characters found in subject: U+2060\n"
+        );
+    }
+
+    #[test]
+    fn scan_content_deny_invisible_spares_emoji() {
+        let config = Config {
+            options: Options {
+                deny_invisible: true,
+                ..Options::default()
+            },
+            blacklist: Blacklist::from_matchers(vec![]),
+            sources: Sources::default(),
+        };
+
+        for subject in [
+            "I \u{2764}\u{FE0F} you",
+            "Family \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}",
+            "Go \u{1F3F4}\u{E0067}\u{E0062}\u{E0073}\u{E0063}\u{E0074}\u{E007F}",
+            "Pride \u{1F3F3}\u{FE0F}\u{200D}\u{1F308}",
+            "Press 1\u{FE0F}\u{20E3}",
+        ] {
+            let mut verdict = Verdict::default();
+            let mut err_buf = Vec::new();
+            scan_content(
+                Some(subject),
+                &Target::Subject,
+                &config,
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+            assert!(verdict.allows(0), "{}", subject);
+            assert!(
+                String::from_utf8(err_buf)
+                    .unwrap()
+                    .starts_with("Invisible characters stripped from subject:")
+            );
+        }
+
+        for subject in [
+            "I \u{2764}\u{FE0F}\u{200B} you",
+            "V\u{200D}iagra",
+            "Vi\u{FE0F}agra",
+            "\u{2764}\u{200D}Viagra",
+            "Via\u{E0067}\u{E007F}gra",
+            "Go \u{1F3F4}\u{E0067}\u{E0062}",
+        ] {
+            let mut verdict = Verdict::default();
+            let mut err_buf = Vec::new();
+            scan_content(
+                Some(subject),
+                &Target::Subject,
+                &config,
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+            assert!(!verdict.allows(0), "{}", subject);
+        }
+    }
+
+    #[test]
+    fn scan_content_deny_invisible_only_applies_to_subject() {
+        let config = Config {
+            options: Options {
+                deny_invisible: true,
+                ..Options::default()
+            },
+            blacklist: Blacklist::from_matchers(vec![]),
+            sources: Sources::default(),
+        };
+
+        for target in [
+            Target::Body,
+            Target::FromName,
+            Target::Header("x-mailer".to_string()),
+            Target::Attachment,
+        ] {
+            let mut verdict = Verdict::default();
+            let mut err_buf = Vec::new();
+            scan_content(
+                Some("News\u{00AD}letter"),
+                &target,
+                &config,
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+            assert!(verdict.allows(0), "{}", target);
+            assert_eq!(
+                String::from_utf8(err_buf).unwrap(),
+                format!("Invisible characters stripped from {}: U+00AD\n", target)
+            );
+        }
+    }
+
+    #[test]
+    fn scan_content_leet_log_shows_original_text() {
+        let blacklist = Blacklist::from_matchers(vec![Matcher::Leet("viagra".to_string())]);
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Cheap v1@gr4 here"),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(!verdict.allows(0));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Forbidden leetspeak literal found in subject: viagra (test.txt, line #1) as: v1@gr4\n"
+        );
+    }
+
+    #[test]
+    fn scan_content_allow_list_overrides_deny() {
+        let blacklist = Blacklist::from_matchers(vec![Matcher::Literal("invoice".to_string())]);
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Your invoice from ACME Corp Invoice #42"),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(!verdict.allows(0));
+
+        scan_content(
+            Some("Your invoice from ACME Corp Invoice #42"),
+            &Target::Subject,
+            &config(Blacklist::with_modifiers(
+                vec![Matcher::Literal("ACME Corp Invoice #".to_string())],
+                Modifiers {
+                    allow: true,
+                    ..Modifiers::default()
+                },
+            )),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(verdict.denies(0));
+        assert!(verdict.allowed);
+        assert!(verdict.allows(0));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Forbidden literal found in subject: invoice (test.txt, line #1)\n\
+             Allowed literal found in subject: ACME Corp Invoice # (test.txt, line #1)\n"
+        );
+    }
+
+    #[test]
+    fn scan_content_allow_list_alone_allows() {
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("ACME Corp Invoice #42"),
+            &Target::Subject,
+            &config(Blacklist::with_modifiers(
+                vec![Matcher::Literal("ACME".to_string())],
+                Modifiers {
+                    allow: true,
+                    ..Modifiers::default()
+                },
+            )),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(!verdict.denies(0));
+        assert!(verdict.allows(0));
+    }
+
+    #[test]
+    fn scan_content_sums_weights() {
+        let mut blacklist = Blacklist::from_matchers(vec![
+            Matcher::Literal("cheap".to_string()),
+            Matcher::Literal("pills".to_string()),
+            Matcher::Literal("casino".to_string()),
+        ]);
+        blacklist.set_weights(&[2, 3, 10]);
+        let config = config(blacklist);
+
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("cheap pills"),
+            &Target::Subject,
+            &config,
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert_eq!(verdict.score, 5);
+        assert!(verdict.denies(4));
+        assert!(!verdict.denies(5));
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Forbidden literal found in subject: cheap (test.txt, line #1, weight 2)\n\
+             Forbidden literal found in subject: pills (test.txt, line #2, weight 3)\n"
+        );
+    }
+
+    #[test]
+    fn scan_content_negative_weights_offset_hits() {
+        let mut blacklist = Blacklist::from_matchers(vec![
+            Matcher::Literal("invoice".to_string()),
+            Matcher::Literal("Re:".to_string()),
+        ]);
+        blacklist.set_weights(&[3, -2]);
+        let config = config(blacklist);
+
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("Re: invoice"),
+            &Target::Subject,
+            &config,
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert_eq!(verdict.score, 1);
+        assert!(verdict.allows(1));
+        assert!(!verdict.allows(0));
+    }
+
+    #[test]
+    fn scan_content_most_severe_reply_wins() {
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        for (pattern, code, text) in [
+            ("deals", 550, "5.7.1 Marketing not wanted"),
+            ("paypal", 554, "5.7.1 Message refused: suspected phishing"),
+            ("offer", 551, "5.7.1 Whatever"),
+        ] {
+            scan_content(
+                Some("Special paypal offer"),
+                &Target::Subject,
+                &config(Blacklist::with_modifiers(
+                    vec![Matcher::Literal(pattern.to_string())],
+                    Modifiers {
+                        reply: Reply {
+                            code,
+                            text: text.to_string(),
+                        },
+                        ..Modifiers::default()
+                    },
+                )),
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            verdict.worst.unwrap().reply.to_string(),
+            "554 5.7.1 Message refused: suspected phishing"
+        );
+    }
+
+    #[test]
+    fn scan_content_reject_outweighs_tempfail() {
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        for (pattern, action, code) in [
+            ("lottery", Action::Tempfail, 451),
+            ("winner", Action::Reject, 550),
+            ("prize", Action::Tempfail, 452),
+        ] {
+            scan_content(
+                Some("Lottery winner prize"),
+                &Target::Subject,
+                &config(Blacklist::with_modifiers(
+                    vec![Matcher::FoldedLiteral(pattern.to_string())],
+                    Modifiers {
+                        action,
+                        reply: Reply {
+                            code,
+                            text: String::new(),
+                        },
+                        ..Modifiers::default()
+                    },
+                )),
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+        }
+        let worst = verdict.worst.unwrap();
+        assert_eq!(worst.action, Action::Reject);
+        assert_eq!(worst.reply.code, 550);
+    }
+
+    #[test]
+    fn scan_content_offsetting_hits_dont_choose_the_action() {
+        let mut disconnect = Blacklist::with_modifiers(
+            vec![Matcher::Literal("newsletter".to_string())],
+            Modifiers {
+                action: Action::Disconnect,
+                reply: Reply {
+                    code: 421,
+                    text: String::new(),
+                },
+                ..Modifiers::default()
+            },
+        );
+        disconnect.set_weights(&[-5]);
+
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        for blacklist in [
+            disconnect,
+            Blacklist::from_matchers(vec![Matcher::Literal("casino".to_string())]),
+        ] {
+            scan_content(
+                Some("casino newsletter"),
+                &Target::Subject,
+                &config(blacklist),
+                &mut verdict,
+                &mut err_buf,
+            )
+            .unwrap();
+        }
+        assert_eq!(verdict.score, -4);
+        let (action, reply) = verdict.outcome(-5).unwrap();
+        assert_eq!(action, Action::Reject);
+        assert_eq!(reply.code, 550);
     }
 
     #[test]
     fn scan_content_regex_match_denies() {
-        let blacklist = vec![Matcher::RegExp(regex::Regex::new(r"sp[a@]m").unwrap())];
-        let mut allow = true;
+        let blacklist = Blacklist::from_matchers(vec![Matcher::RegExp(
+            regex::Regex::new(r"sp[a@]m").unwrap(),
+        )]);
+        let mut verdict = Verdict::default();
+        let mut err_buf = Vec::new();
+        scan_content(
+            Some("This is sp@m content"),
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
+            &mut err_buf,
+        )
+        .unwrap();
+        assert!(!verdict.allows(0));
+    }
+
+    #[test]
+    fn scan_content_regex_log_names_origin() {
+        let blacklist = Blacklist::from_matchers(vec![
+            Matcher::RegExp(regex::Regex::new(r"^Re:").unwrap()),
+            Matcher::RegExp(regex::Regex::new(r"sp[a@]m").unwrap()),
+        ]);
+        let mut verdict = Verdict::default();
         let mut err_buf = Vec::new();
         scan_content(
             Some("This is sp@m content"),
-            "body",
-            &blacklist,
-            &mut allow,
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
             &mut err_buf,
         )
         .unwrap();
-        assert!(!allow);
+        assert_eq!(
+            String::from_utf8(err_buf).unwrap(),
+            "Forbidden regex found in subject: sp[a@]m (test.txt, line #2)\n"
+        );
     }
 
     #[test]
     fn scan_content_regex_no_match_allows() {
-        let blacklist = vec![Matcher::RegExp(regex::Regex::new(r"sp[a@]m").unwrap())];
-        let mut allow = true;
+        let blacklist = Blacklist::from_matchers(vec![Matcher::RegExp(
+            regex::Regex::new(r"sp[a@]m").unwrap(),
+        )]);
+        let mut verdict = Verdict::default();
         let mut err_buf = Vec::new();
         scan_content(
             Some("This is clean content"),
-            "body",
-            &blacklist,
-            &mut allow,
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
             &mut err_buf,
         )
         .unwrap();
-        assert!(allow);
+        assert!(verdict.allows(0));
     }
 
     #[test]
     fn scan_content_empty_blacklist_always_allows() {
-        let blacklist: Vec<Matcher> = vec![];
-        let mut allow = true;
+        let blacklist = Blacklist::from_matchers(vec![]);
+        let mut verdict = Verdict::default();
         let mut err_buf = Vec::new();
         scan_content(
             Some("any content at all"),
-            "subject",
-            &blacklist,
-            &mut allow,
+            &Target::Subject,
+            &config(blacklist),
+            &mut verdict,
             &mut err_buf,
         )
         .unwrap();
-        assert!(allow);
+        assert!(verdict.allows(0));
     }
 }
@@ -0,0 +1,202 @@
+use crate::cli::Sources;
+use inotify::{EventMask, Events, Inotify, WatchDescriptor, WatchMask};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+const DEBOUNCE: Duration = Duration::from_millis(500);
+const RETRY: Duration = Duration::from_secs(5);
+
+pub(crate) fn spawn(sources: Sources, reload: Arc<AtomicBool>) -> io::Result<Sender<Sources>> {
+    let inotify = Inotify::init()?;
+    let watches = Arc::new(Mutex::new(watch(inotify.watches(), &sources)));
+    let (updates, receiver) = mpsc::channel();
+
+    {
+        let watches = watches.clone();
+        let reload = reload.clone();
+
+        thread::spawn(move || {
+            if let Err(err) = read(inotify, &watches, &reload) {
+                eprintln!("Watching pattern list files failed: {}", err);
+            }
+        });
+    }
+
+    thread::spawn(move || update(receiver, &watches, &reload));
+
+    Ok(updates)
+}
+
+struct Watches {
+    handle: inotify::Watches,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    files: HashSet<(PathBuf, OsString)>,
+    whole_dirs: HashSet<PathBuf>,
+    failed: Vec<PathBuf>,
+}
+
+impl Watches {
+    fn add(&mut self, dir: PathBuf) -> io::Result<()> {
+        let wd = self.handle.add(
+            &dir,
+            WatchMask::CLOSE_WRITE
+                | WatchMask::MODIFY
+                | WatchMask::CREATE
+                | WatchMask::DELETE
+                | WatchMask::MOVED_FROM
+                | WatchMask::MOVED_TO,
+        )?;
+
+        self.dirs.insert(wd, dir);
+        Ok(())
+    }
+
+    fn retry(&mut self) -> bool {
+        let mut added = false;
+
+        for dir in std::mem::take(&mut self.failed) {
+            match self.add(dir.clone()) {
+                Ok(()) => added = true,
+                Err(_) => self.failed.push(dir),
+            }
+        }
+
+        added
+    }
+
+    fn relevant(&self, events: Events) -> bool {
+        let mut relevant = false;
+
+        for event in events {
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                relevant = true;
+                continue;
+            }
+
+            if let Some(dir) = self.dirs.get(&event.wd) {
+                relevant |= self.whole_dirs.contains(dir)
+                    || event
+                        .name
+                        .is_some_and(|name| self.files.contains(&(dir.clone(), name.to_owned())));
+            }
+        }
+
+        relevant
+    }
+}
+
+fn lock(watches: &Mutex<Watches>) -> MutexGuard<'_, Watches> {
+    // The other thread panicked, but the watches are still consistent.
+    watches.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn read(mut inotify: Inotify, watches: &Mutex<Watches>, reload: &AtomicBool) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+
+    loop {
+        let events = inotify.read_events_blocking(&mut buffer)?;
+
+        if lock(watches).relevant(events) {
+            // Reload only once the files stopped changing.
+            loop {
+                thread::sleep(DEBOUNCE);
+
+                if !drain(&mut inotify, &mut buffer, watches)? {
+                    break;
+                }
+            }
+
+            reload.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn drain(inotify: &mut Inotify, buffer: &mut [u8], watches: &Mutex<Watches>) -> io::Result<bool> {
+    let mut relevant = false;
+
+    loop {
+        match inotify.read_events(buffer) {
+            Ok(events) => relevant |= lock(watches).relevant(events),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(relevant),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn update(updates: Receiver<Sources>, watches: &Mutex<Watches>, reload: &AtomicBool) {
+    loop {
+        let failed = !lock(watches).failed.is_empty();
+
+        let received = match failed {
+            true => updates.recv_timeout(RETRY),
+            false => updates.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(sources) => {
+                let mut guard = lock(watches);
+                let watches = &mut *guard;
+
+                for (wd, _) in watches.dirs.drain() {
+                    // Fails if the directory is gone, nothing to do then.
+                    watches.handle.remove(wd).ok();
+                }
+
+                *watches = watch(watches.handle.clone(), &sources);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                // The files may have changed while unwatched.
+                if lock(watches).retry() {
+                    reload.store(true, Ordering::Relaxed);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+fn watch(handle: inotify::Watches, sources: &Sources) -> Watches {
+    let mut watches = Watches {
+        handle,
+        dirs: HashMap::new(),
+        files: HashSet::new(),
+        whole_dirs: sources.dirs.iter().cloned().collect(),
+        failed: Vec::new(),
+    };
+
+    for file in &sources.files {
+        let dir = match file.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        watches.files.insert((
+            dir.to_path_buf(),
+            file.file_name().unwrap_or_default().to_owned(),
+        ));
+    }
+
+    let dirs = watches
+        .files
+        .iter()
+        .map(|(dir, _)| dir.clone())
+        .chain(sources.dirs.iter().cloned())
+        .collect::<HashSet<_>>();
+
+    // Directories rather than files are watched, so that replacing a file via rename is noticed, too.
+    for dir in dirs {
+        if let Err(err) = watches.add(dir.clone()) {
+            eprintln!("Watching {} failed, retrying: {}", dir.display(), err);
+            watches.failed.push(dir);
+        }
+    }
+
+    watches
+}
@@ -154,3 +154,961 @@ fn link_disconnect_removes_session() {
     // After disconnect the session buffer is gone; commit treats missing session as allow
     assert!(stdout.contains("filter-result|sess7|tok7|proceed\n"));
 }
+
+#[test]
+fn normalize_modifier_catches_confusable_subject() {
+    let path = std::env::temp_dir().join("filter_normalize_subject.txt");
+    fs::write(&path, "paypal\n").unwrap();
+
+    let input = make_session_input(
+        "sess8",
+        "tok8",
+        &[
+            "From: sender@example.com",
+            "Subject: Your раураl account",
+            "",
+            "Normal body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&["literal", path.to_str().unwrap()], &input);
+    assert!(stdout.contains("filter-result|sess8|tok8|proceed\n"));
+
+    let (stdout, _) = run_filter(&["literal,normalize", path.to_str().unwrap()], &input);
+    assert!(stdout.contains("filter-result|sess8|tok8|reject|550 Blacklisted keyphrase found\n"));
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn deny_invisible_rejects_zero_width_subject() {
+    let input = make_session_input(
+        "sess9",
+        "tok9",
+        &[
+            "From: sender@example.com",
+            "Subject: Hello\u{200B} there",
+            "",
+            "Normal body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&[], &input);
+    assert!(stdout.contains("filter-result|sess9|tok9|proceed\n"));
+    assert!(stderr.contains("Invisible characters stripped from subject: U+200B\n"));
+
+    let (stdout, _) = run_filter(&["--deny-invisible"], &input);
+    assert!(stdout.contains("filter-result|sess9|tok9|reject|550 Blacklisted keyphrase found\n"));
+}
+
+#[test]
+fn deny_invisible_outweighs_softer_lists() {
+    let path = std::env::temp_dir().join("filter_invisible_soft.txt");
+    fs::write(&path, "free\n").unwrap();
+    let input = make_session_input(
+        "sess46",
+        "tok46",
+        &[
+            "From: sender@example.com",
+            "Subject: free V\u{200B}iagra",
+            "",
+            "Body.",
+        ],
+    );
+
+    for modifier in ["tag", "junk", "tempfail"] {
+        let kind = format!("literal,{}", modifier);
+        let (stdout, stderr) =
+            run_filter(&["--deny-invisible", &kind, path.to_str().unwrap()], &input);
+        assert!(
+            stdout.contains("filter-result|sess46|tok46|reject|550 Blacklisted keyphrase found\n"),
+            "{}",
+            modifier
+        );
+        assert!(stdout.contains("filter-dataline|sess46|tok46|Subject: free V\u{200B}iagra\n"));
+        assert!(stderr.contains("Denying\n"));
+    }
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn allow_list_overrides_blacklisted_literal() {
+    let deny = std::env::temp_dir().join("filter_allow_override_deny.txt");
+    let allow = std::env::temp_dir().join("filter_allow_override_allow.txt");
+    fs::write(&deny, "invoice\n").unwrap();
+    fs::write(&allow, "ACME Corp Invoice #\n").unwrap();
+
+    let args = [
+        "literal",
+        deny.to_str().unwrap(),
+        "allow-literal",
+        allow.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess10",
+        "tok10",
+        &[
+            "From: billing@acme.example",
+            "Subject: Your invoice: ACME Corp Invoice #42",
+            "",
+            "Normal body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess10|tok10|proceed\n"));
+    assert!(stderr.contains("Forbidden literal found in subject: invoice"));
+    assert!(stderr.contains("Allowed literal found in subject: ACME Corp Invoice #"));
+    assert!(stderr.contains("Denial overridden by allow-list\n"));
+
+    let input = make_session_input(
+        "sess11",
+        "tok11",
+        &[
+            "From: scam@example.com",
+            "Subject: Your invoice is overdue",
+            "",
+            "Normal body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess11|tok11|reject|550 Blacklisted keyphrase found\n"));
+
+    fs::remove_file(&deny).ok();
+    fs::remove_file(&allow).ok();
+}
+
+#[test]
+fn weak_signals_add_up_to_threshold() {
+    let path = std::env::temp_dir().join("filter_weighted_subject.txt");
+    fs::write(&path, "2\tcheap\n2\tpills\n5\tcasino\n").unwrap();
+    let args = ["--threshold=3", "literal", path.to_str().unwrap()];
+
+    let input = make_session_input(
+        "sess12",
+        "tok12",
+        &["From: a@example.com", "Subject: cheap flights", "", "Body."],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess12|tok12|proceed\n"));
+    assert!(stderr.contains("Score: 2 (threshold: 3)\n"));
+
+    let input = make_session_input(
+        "sess13",
+        "tok13",
+        &["From: a@example.com", "Subject: cheap pills", "", "Body."],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess13|tok13|reject|550 Blacklisted keyphrase found\n"));
+    assert!(stderr.contains("Score: 4 (threshold: 3)\n"));
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn per_list_reply_is_used_for_rejection() {
+    let phishing = std::env::temp_dir().join("filter_reply_phishing.txt");
+    let marketing = std::env::temp_dir().join("filter_reply_marketing.txt");
+    fs::write(&phishing, "paypal\n").unwrap();
+    fs::write(&marketing, "offer\n").unwrap();
+    let args = [
+        "literal,reply=550 5.7.1 Message refused: suspected phishing",
+        phishing.to_str().unwrap(),
+        "literal,reply=550 5.7.1 Marketing, no thanks",
+        marketing.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess14",
+        "tok14",
+        &[
+            "From: a@example.com",
+            "Subject: Verify your paypal account",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains(
+        "filter-result|sess14|tok14|reject|550 5.7.1 Message refused: suspected phishing\n"
+    ));
+
+    let input = make_session_input(
+        "sess15",
+        "tok15",
+        &["From: a@example.com", "Subject: Special offer", "", "Body."],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess15|tok15|reject|550 5.7.1 Marketing, no thanks\n"));
+
+    fs::remove_file(&phishing).ok();
+    fs::remove_file(&marketing).ok();
+}
+
+#[test]
+fn tempfail_list_defers_with_4xx() {
+    let soft = std::env::temp_dir().join("filter_tempfail_soft.txt");
+    let hard = std::env::temp_dir().join("filter_tempfail_hard.txt");
+    fs::write(&soft, "lottery\n").unwrap();
+    fs::write(&hard, "viagra\n").unwrap();
+    let args = [
+        "literal,tempfail",
+        soft.to_str().unwrap(),
+        "literal",
+        hard.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess16",
+        "tok16",
+        &[
+            "From: a@example.com",
+            "Subject: Your lottery results",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains(
+        "filter-result|sess16|tok16|reject|451 4.7.1 Blacklisted keyphrase found, try again later\n"
+    ));
+    assert!(stderr.contains("Deferring\n"));
+
+    let input = make_session_input(
+        "sess17",
+        "tok17",
+        &[
+            "From: a@example.com",
+            "Subject: lottery viagra",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess17|tok17|reject|550 Blacklisted keyphrase found\n"));
+
+    fs::remove_file(&soft).ok();
+    fs::remove_file(&hard).ok();
+}
+
+#[test]
+fn tag_list_rewrites_subject_and_delivers() {
+    let path = std::env::temp_dir().join("filter_tag_subject.txt");
+    fs::write(&path, "lottery\n").unwrap();
+    let args = ["literal,tag", path.to_str().unwrap()];
+
+    let input = make_session_input(
+        "sess18",
+        "tok18",
+        &[
+            "From: a@example.com",
+            "Subject: Your lottery",
+            " results",
+            "To: b@example.com",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains(concat!(
+        "filter-dataline|sess18|tok18|From: a@example.com\n",
+        "filter-dataline|sess18|tok18|Subject: [SUSPECT] Your lottery results\n",
+        "filter-dataline|sess18|tok18|To: b@example.com\n",
+        "filter-dataline|sess18|tok18|\n",
+        "filter-dataline|sess18|tok18|Body.\n",
+        "filter-dataline|sess18|tok18|.\n",
+    )));
+    assert!(!stdout.contains("|Subject: Your lottery\n"));
+    assert!(stdout.contains("filter-result|sess18|tok18|proceed\n"));
+    assert!(stderr.contains("Tagging\n"));
+
+    let input = make_session_input(
+        "sess19",
+        "tok19",
+        &[
+            "From: a@example.com",
+            "Subject: Gewinn in der lottery für Sie",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&["--tag-prefix=[SPAM?]", args[0], args[1]], &input);
+    assert!(stdout.contains(
+        "filter-dataline|sess19|tok19|Subject: =?UTF-8?B?W1NQQU0/XSBHZXdpbm4gaW4gZGVyIGxvdHRlcnkgZsO8ciBTaWU=?=\n"
+    ));
+    assert!(stdout.contains("filter-result|sess19|tok19|proceed\n"));
+
+    let input = make_session_input(
+        "sess20",
+        "tok20",
+        &["From: a@example.com", "Subject: Harmless", "", "Body."],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-dataline|sess20|tok20|Subject: Harmless\n"));
+
+    let input = make_session_input(
+        "sess45",
+        "tok45",
+        &[
+            "From: a@example.com",
+            "Subject: Congratulations, you have been selected as the winner of our",
+            " annual lottery, please reply with your bank details to claim the prize",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    let subject = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("filter-dataline|sess45|tok45|Subject:"))
+        .take_while(|line| !line.ends_with("|tok45|"))
+        .map(|line| line.strip_prefix("filter-dataline|sess45|tok45|").unwrap())
+        .collect::<Vec<_>>();
+    assert!(subject.len() > 1);
+    assert!(subject.iter().all(|line| line.len() <= 78));
+    assert_eq!(
+        subject.concat(),
+        "Subject: [SUSPECT] Congratulations, you have been selected as the winner of our \
+         annual lottery, please reply with your bank details to claim the prize"
+    );
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn verdict_header_is_inserted_before_body() {
+    let path = std::env::temp_dir().join("phishing.txt");
+    fs::write(&path, "3\tverify\n4\taccount\n").unwrap();
+    let args = [
+        "--verdict-header",
+        "--threshold=10",
+        "literal",
+        path.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess21",
+        "tok21",
+        &[
+            "From: a@example.com",
+            "Subject: verify your account",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains(concat!(
+        "filter-dataline|sess21|tok21|Subject: verify your account\n",
+        "filter-dataline|sess21|tok21|X-Subjectstrings: hit; list=phishing; pattern=line1, line2; score=7\n",
+        "filter-dataline|sess21|tok21|\n",
+        "filter-dataline|sess21|tok21|Body.\n",
+    )));
+    assert!(stdout.contains("filter-result|sess21|tok21|proceed\n"));
+
+    let input = make_session_input(
+        "sess22",
+        "tok22",
+        &["From: a@example.com", "Subject: Hello", "", "Body."],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-dataline|sess22|tok22|X-Subjectstrings: clean; score=0\n"));
+
+    let input = make_session_input(
+        "sess44",
+        "tok44",
+        &[
+            "From: a@example.com",
+            "x-subjectstrings: clean;",
+            " score=0",
+            "Subject: verify your account",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains(concat!(
+        "filter-dataline|sess44|tok44|From: a@example.com\n",
+        "filter-dataline|sess44|tok44|Subject: verify your account\n",
+    )));
+    assert_eq!(stdout.matches("X-Subjectstrings:").count(), 1);
+    assert!(!stdout.contains("score=0"));
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn dry_run_logs_but_proceeds() {
+    let live = std::env::temp_dir().join("filter_dry_run_live.txt");
+    let trial = std::env::temp_dir().join("filter_dry_run_trial.txt");
+    fs::write(&live, "viagra\n").unwrap();
+    fs::write(&trial, "lottery\n").unwrap();
+
+    let input = make_session_input(
+        "sess23",
+        "tok23",
+        &["From: a@example.com", "Subject: viagra", "", "Body."],
+    );
+    let (stdout, stderr) = run_filter(&["--dry-run", "literal", live.to_str().unwrap()], &input);
+    assert!(stdout.contains("filter-result|sess23|tok23|proceed\n"));
+    assert!(stderr.contains(&format!(
+        "Would deny, matching patterns: ({}, line #1)\n",
+        live.display()
+    )));
+
+    let args = [
+        "literal",
+        live.to_str().unwrap(),
+        "literal,dry-run",
+        trial.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess24",
+        "tok24",
+        &["From: a@example.com", "Subject: lottery", "", "Body."],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess24|tok24|proceed\n"));
+    assert!(stderr.contains(&format!(
+        "Would deny, matching patterns: ({}, line #1)\n",
+        trial.display()
+    )));
+
+    let input = make_session_input(
+        "sess25",
+        "tok25",
+        &[
+            "From: a@example.com",
+            "Subject: viagra lottery",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess25|tok25|reject|550 Blacklisted keyphrase found\n"));
+    assert!(!stderr.contains("Would deny"));
+
+    fs::remove_file(&live).ok();
+    fs::remove_file(&trial).ok();
+}
+
+#[test]
+fn junk_list_marks_mail_as_spam() {
+    let junk = std::env::temp_dir().join("filter_junk_soft.txt");
+    let tag = std::env::temp_dir().join("filter_junk_tag.txt");
+    fs::write(&junk, "webinar\n").unwrap();
+    fs::write(&tag, "free\n").unwrap();
+    let args = [
+        "literal,junk",
+        junk.to_str().unwrap(),
+        "literal,tag",
+        tag.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess26",
+        "tok26",
+        &[
+            "From: a@example.com",
+            "Subject: Join our free webinar",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess26|tok26|junk\n"));
+    assert!(stdout.contains("filter-dataline|sess26|tok26|Subject: Join our free webinar\n"));
+    assert!(stderr.contains("Junking\n"));
+
+    fs::remove_file(&junk).ok();
+    fs::remove_file(&tag).ok();
+}
+
+#[test]
+fn disconnect_list_drops_connection() {
+    let path = std::env::temp_dir().join("filter_disconnect_subject.txt");
+    fs::write(&path, "Invoice_2024.pdf.exe\n").unwrap();
+    let args = ["literal,disconnect", path.to_str().unwrap()];
+
+    let mut input = make_session_input(
+        "sess27",
+        "tok27",
+        &[
+            "From: a@example.com",
+            "Subject: Invoice_2024.pdf.exe",
+            "",
+            "Body.",
+        ],
+    );
+    // data for the dropped session must not be buffered anymore
+    writeln!(
+        input,
+        "filter|1|1000|smtp-in|data-line|sess27|tok27|Subject: Invoice_2024.pdf.exe"
+    )
+    .unwrap();
+    writeln!(input, "filter|1|1000|smtp-in|commit|sess27|tok27").unwrap();
+
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains(
+        "filter-result|sess27|tok27|disconnect|421 4.7.0 Blacklisted keyphrase found, closing connection\n"
+    ));
+    assert!(stdout.contains("filter-result|sess27|tok27|proceed\n"));
+    assert!(stderr.contains("Disconnecting\n"));
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn pattern_metadata_is_logged() {
+    let path = std::env::temp_dir().join("filter_metadata_subject.txt");
+    fs::write(
+        &path,
+        "# known phishing subjects\npaypal\tid=PH-12 ticket=OPS-7\n\\#winner\n",
+    )
+    .unwrap();
+    let args = ["literal", path.to_str().unwrap()];
+
+    let input = make_session_input(
+        "sess28",
+        "tok28",
+        &[
+            "From: a@example.com",
+            "Subject: Your paypal account",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess28|tok28|reject|550 Blacklisted keyphrase found\n"));
+    assert!(stderr.contains(&format!(
+        "Forbidden literal found in subject: paypal ({}, line #2, id=PH-12, ticket=OPS-7)\n",
+        path.display()
+    )));
+
+    let input = make_session_input(
+        "sess29",
+        "tok29",
+        &[
+            "From: a@example.com",
+            "Subject: known phishing subjects",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess29|tok29|proceed\n"));
+
+    let input = make_session_input(
+        "sess30",
+        "tok30",
+        &[
+            "From: a@example.com",
+            "Subject: You are a #winner",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess30|tok30|reject|550 Blacklisted keyphrase found\n"));
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn expired_patterns_are_skipped() {
+    let path = std::env::temp_dir().join("filter_expiry_subject.txt");
+    fs::write(
+        &path,
+        "Black Friday deals\texpires=2020-12-01\nCyber Monday\texpires=2999-12-01\n",
+    )
+    .unwrap();
+    let args = ["literal", path.to_str().unwrap()];
+
+    let mut input = make_session_input(
+        "sess31",
+        "tok31",
+        &[
+            "From: a@example.com",
+            "Subject: Black Friday deals",
+            "",
+            "Body.",
+        ],
+    );
+    input.extend(make_session_input(
+        "sess32",
+        "tok32",
+        &[
+            "From: a@example.com",
+            "Subject: Black Friday deals",
+            "",
+            "Body.",
+        ],
+    ));
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess31|tok31|proceed\n"));
+    assert!(stdout.contains("filter-result|sess32|tok32|proceed\n"));
+    assert_eq!(
+        stderr
+            .matches("Expired literal would have matched in subject: Black Friday deals")
+            .count(),
+        1
+    );
+
+    let input = make_session_input(
+        "sess33",
+        "tok33",
+        &["From: a@example.com", "Subject: Cyber Monday", "", "Body."],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess33|tok33|reject|550 Blacklisted keyphrase found\n"));
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn include_error_reports_chain() {
+    let dir = std::env::temp_dir().join("filter_include_chain");
+    fs::create_dir_all(&dir).unwrap();
+    let top = dir.join("top.txt");
+    let middle = dir.join("middle.txt");
+    let bottom = dir.join("bottom.txt");
+    fs::write(&top, "a\n#include middle.txt\n").unwrap();
+    fs::write(&middle, "#include-regex bottom.txt\n").unwrap();
+    fs::write(&bottom, "ok\n(unclosed\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_opensmtpd-filter-subjectstrings"))
+        .args(["literal", top.to_str().unwrap()])
+        .output()
+        .expect("Failed to run binary");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.starts_with(&format!(
+        "Invalid regular expression ({}, line #2): ",
+        bottom.display()
+    )));
+    assert!(stderr.ends_with(&format!(
+        "\n  included from {}, line #1\n  included from {}, line #2 (CLI argument #2)\n",
+        middle.display(),
+        top.display()
+    )));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn dir_kind_error_names_file() {
+    let dir = std::env::temp_dir().join("filter_dir_error");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.regex"), "fine\n").unwrap();
+    fs::write(dir.join("b.regex"), "ok\n(unclosed\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_opensmtpd-filter-subjectstrings"))
+        .args(["regex-dir", dir.to_str().unwrap()])
+        .output()
+        .expect("Failed to run binary");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.starts_with(&format!(
+        "Invalid regular expression (CLI argument #2, {}, line #2): ",
+        dir.join("b.regex").display()
+    )));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn sighup_reloads_pattern_lists() {
+    use std::io::{BufRead, BufReader};
+
+    let path = std::env::temp_dir().join("filter_reload_subject.txt");
+    fs::write(&path, "viagra\n").unwrap();
+
+    let mut child = filter_cmd(&["literal", path.to_str().unwrap()]);
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut result = |session: &str, subject: &str| {
+        let input = make_session_input(
+            session,
+            "tok",
+            &[
+                "From: a@example.com",
+                &format!("Subject: {}", subject),
+                "",
+                "Body.",
+            ],
+        );
+        stdin.write_all(&input).unwrap();
+        stdin.flush().unwrap();
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            stdout.read_line(&mut line).unwrap();
+            if line.starts_with("filter-result|") {
+                return line.clone();
+            }
+        }
+    };
+
+    assert_eq!(
+        result("sess34", "lottery"),
+        "filter-result|sess34|tok|proceed\n"
+    );
+
+    fs::write(&path, "lottery\n").unwrap();
+    let status = Command::new("kill")
+        .args(["-HUP", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(
+        result("sess35", "lottery"),
+        "filter-result|sess35|tok|reject|550 Blacklisted keyphrase found\n"
+    );
+
+    fs::write(&path, "\\no\tid=x expires=never\n").unwrap();
+    Command::new("kill")
+        .args(["-HUP", &child.id().to_string()])
+        .status()
+        .unwrap();
+
+    assert_eq!(
+        result("sess36", "lottery"),
+        "filter-result|sess36|tok|reject|550 Blacklisted keyphrase found\n"
+    );
+
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Pattern lists reloaded\n"));
+    assert!(stderr.contains("Invalid expiry date (CLI argument #2, line #1)"));
+    assert!(stderr.contains("Reload failed, keeping the previous pattern lists\n"));
+
+    fs::remove_file(&path).ok();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn watch_reloads_changed_pattern_lists() {
+    use std::io::{BufRead, BufReader};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join("filter_watch_lists");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("subject.txt");
+    fs::write(&path, "viagra\n").unwrap();
+
+    let mut child = filter_cmd(&["--watch", "literal", path.to_str().unwrap()]);
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut result = |session: &str, subject: &str| {
+        let input = make_session_input(
+            session,
+            "tok",
+            &[
+                "From: a@example.com",
+                &format!("Subject: {}", subject),
+                "",
+                "Body.",
+            ],
+        );
+        stdin.write_all(&input).unwrap();
+        stdin.flush().unwrap();
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            stdout.read_line(&mut line).unwrap();
+            if line.starts_with("filter-result|") {
+                return line.clone();
+            }
+        }
+    };
+
+    assert_eq!(
+        result("sess37", "lottery"),
+        "filter-result|sess37|tok|proceed\n"
+    );
+
+    // replace atomically, several times in a row
+    for pattern in ["casino\n", "poker\n", "lottery\n"] {
+        let tmp = dir.join(".subject.txt.tmp");
+        fs::write(&tmp, pattern).unwrap();
+        fs::rename(&tmp, &path).unwrap();
+    }
+
+    sleep(Duration::from_millis(1500));
+
+    assert_eq!(
+        result("sess38", "lottery"),
+        "filter-result|sess38|tok|reject|550 Blacklisted keyphrase found\n"
+    );
+
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("Pattern lists reloaded\n").count(), 1);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn header_bound_lists_scan_other_headers() {
+    let mailer = std::env::temp_dir().join("filter_header_mailer.txt");
+    let names = std::env::temp_dir().join("filter_header_names.txt");
+    fs::write(&mailer, "BulkMailer\n").unwrap();
+    fs::write(&names, "PayPal\n").unwrap();
+    let args = [
+        "literal,header=X-Mailer",
+        mailer.to_str().unwrap(),
+        "word,from-name",
+        names.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess39",
+        "tok39",
+        &[
+            "From: \"PayPal Support\" <a@example.com>",
+            "Subject: BulkMailer news",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess39|tok39|reject|550 Blacklisted keyphrase found\n"));
+    assert!(stderr.contains("Forbidden word found in From display name: PayPal ("));
+    assert!(!stderr.contains("found in subject"));
+
+    let input = make_session_input(
+        "sess40",
+        "tok40",
+        &[
+            "From: a@example.com",
+            "Subject: Hello",
+            "X-Mailer: =?UTF-8?Q?BulkMailer_3.0?=",
+            "",
+            "Body.",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess40|tok40|reject|550 Blacklisted keyphrase found\n"));
+    assert!(stderr.contains("Forbidden literal found in header x-mailer: BulkMailer"));
+
+    fs::remove_file(&mailer).ok();
+    fs::remove_file(&names).ok();
+}
+
+#[test]
+fn body_lists_scan_decoded_body_text() {
+    let path = std::env::temp_dir().join("filter_body_phrases.txt");
+    fs::write(&path, "casino\n").unwrap();
+    let args = [
+        "--verdict-header",
+        "iliteral,body,tag",
+        path.to_str().unwrap(),
+    ];
+
+    let input = make_session_input(
+        "sess41",
+        "tok41",
+        &[
+            "From: a@example.com",
+            "Subject: Hello",
+            "Content-Type: multipart/alternative; boundary=b",
+            "",
+            "--b",
+            "Content-Type: text/plain",
+            "",
+            "Nothing to see",
+            "--b",
+            "Content-Type: text/html",
+            "Content-Transfer-Encoding: quoted-printable",
+            "",
+            "<p>Best <b>Casi=",
+            "no</b> in town</p>",
+            "--b--",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args[1..], &input);
+    assert!(stdout.contains("filter-result|sess41|tok41|proceed\n"));
+    assert!(stderr.contains("Forbidden case-insensitive literal found in body: casino ("));
+    assert!(stdout.contains("filter-dataline|sess41|tok41|Subject: [SUSPECT] Hello\n"));
+    assert!(stdout.contains(concat!(
+        "filter-dataline|sess41|tok41|\n",
+        "filter-dataline|sess41|tok41|--b\n",
+    )));
+    assert!(stdout.ends_with(concat!(
+        "filter-dataline|sess41|tok41|--b--\n",
+        "filter-dataline|sess41|tok41|.\n",
+        "filter-result|sess41|tok41|proceed\n",
+    )));
+
+    let (stdout, stderr) = run_filter(&["--body-limit=100", args[1], args[2]], &input);
+    assert!(stdout.contains("filter-dataline|sess41|tok41|Subject: Hello\n"));
+    assert!(stderr.contains("Body not scanned, eMail exceeds 100 bytes\n"));
+    assert!(!stderr.contains("found in body"));
+
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains(concat!(
+        "filter-dataline|sess41|tok41|X-Subjectstrings: hit; list=filter_body_phrases; pattern=line1; score=1;\n",
+        "filter-dataline|sess41|tok41| denied=yes\n",
+        "filter-dataline|sess41|tok41|\n",
+    )));
+
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn attachment_lists_match_file_names() {
+    let path = std::env::temp_dir().join("filter_attachment_names.txt");
+    fs::write(&path, "\\.pdf\\.exe$\n").unwrap();
+    let args = ["regex,attachment,disconnect", path.to_str().unwrap()];
+
+    let input = make_session_input(
+        "sess42",
+        "tok42",
+        &[
+            "From: a@example.com",
+            "Subject: Invoice_2024.pdf.exe",
+            "Content-Type: multipart/mixed; boundary=b",
+            "",
+            "--b",
+            "Content-Type: text/plain",
+            "",
+            "Please find attached Invoice_2024.pdf.exe",
+            "--b",
+            "Content-Type: application/octet-stream",
+            "Content-Disposition: attachment; filename=\"=?UTF-8?B?SW52b2ljZV8yMDI0LnBkZi5leGU=?=\"",
+            "",
+            "TVo=",
+            "--b--",
+        ],
+    );
+    let (stdout, stderr) = run_filter(&args, &input);
+    assert!(stdout.contains(
+        "filter-result|sess42|tok42|disconnect|421 4.7.0 Blacklisted keyphrase found, closing connection\n"
+    ));
+    assert_eq!(
+        stderr
+            .matches("Forbidden regex found in attachment: \\.pdf\\.exe$ (")
+            .count(),
+        1
+    );
+
+    let input = make_session_input(
+        "sess43",
+        "tok43",
+        &[
+            "From: a@example.com",
+            "Subject: Invoice_2024.pdf.exe",
+            "",
+            "No attachment here: Invoice_2024.pdf.exe",
+        ],
+    );
+    let (stdout, _) = run_filter(&args, &input);
+    assert!(stdout.contains("filter-result|sess43|tok43|proceed\n"));
+
+    fs::remove_file(&path).ok();
+}